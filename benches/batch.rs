@@ -0,0 +1,89 @@
+use criterion::{Criterion, black_box, criterion_group, criterion_main};
+
+use Janus::batch;
+use Janus::column;
+use Janus::encoding::Encoding;
+use std::fs;
+
+// Short values as in database cells: the lines of the novel, cut into
+// pieces of at most `max_len` bytes.
+fn short_values(bytes: &[u8], encoding: Encoding, max_len: usize) -> Vec<&[u8]> {
+    let mut values = Vec::new();
+    for line in bytes.split(|&b| b == b'\n') {
+        let mut start = 0;
+        while start < line.len() {
+            let mut end = start;
+            while end < line.len() {
+                let len = encoding.char_len(line, end).unwrap();
+                if end + len - start > max_len {
+                    break;
+                }
+                end += len;
+            }
+            values.push(&line[start..end]);
+            start = end;
+        }
+    }
+    values
+}
+
+fn batch_benchmark(c: &mut Criterion) {
+    let bytes = fs::read("dream_gb2312.txt").unwrap();
+
+    for max_len in [8, 20, 64, 200] {
+        let values = short_values(&bytes, Encoding::Gb2312, max_len);
+
+        c.bench_function(&format!("batch::per_value_is_valid_{}", max_len), |b| {
+            b.iter(|| {
+                black_box(&values)
+                    .iter()
+                    .map(|value| Encoding::Gb2312.is_valid(value))
+                    .collect::<Vec<bool>>()
+            })
+        });
+
+        c.bench_function(&format!("batch::validate_batch_{}", max_len), |b| {
+            b.iter(|| batch::validate_batch(black_box(&values), Encoding::Gb2312))
+        });
+    }
+
+    let values = short_values(&bytes, Encoding::Gb2312, 64);
+
+    // one value in sixteen cut off after a lead byte
+    let invalid: Vec<&[u8]> = values
+        .iter()
+        .enumerate()
+        .map(|(index, value)| {
+            if index % 16 == 0 && value.len() > 1 && value[0] >= 0x80 {
+                &value[..1]
+            } else {
+                value
+            }
+        })
+        .collect();
+    c.bench_function("batch::per_value_errors_invalid", |b| {
+        b.iter(|| {
+            black_box(&invalid)
+                .iter()
+                .map(|value| Encoding::Gb2312.validate(value).err())
+                .collect::<Vec<_>>()
+        })
+    });
+    c.bench_function("batch::validate_batch_errors_invalid", |b| {
+        b.iter(|| batch::validate_batch_errors(black_box(&invalid), Encoding::Gb2312))
+    });
+
+    // the same values as an Arrow string column
+    let mut buffer = Vec::new();
+    let mut offsets = vec![0u32];
+    for value in &values {
+        buffer.extend_from_slice(value);
+        offsets.push(buffer.len() as u32);
+    }
+    c.bench_function("batch::validate_column", |b| {
+        b.iter(|| column::validate_column(black_box(&buffer), &offsets, Encoding::Gb2312).unwrap())
+    });
+}
+
+criterion_group!(benches, batch_benchmark);
+criterion_main!(benches);
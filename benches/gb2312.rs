@@ -30,6 +30,22 @@ fn gb2312_benchmark(c: &mut Criterion) {
     c.bench_function("gb2312::is_gb2312_simd", |b| {
         b.iter(|| gb2312::is_gb2312_simd(black_box(&bytes), black_box(&i32_table)))
     });
+
+    let utf8 = tools::build_utf8_table(&gb2312::build_gb2312_unicode_table());
+
+    c.bench_function("gb2312::simd_then_decode_rs", |b| {
+        b.iter(|| {
+            assert!(gb2312::is_gb2312_simd(black_box(&bytes), &i32_table));
+            encoding_rs::GBK
+                .decode_without_bom_handling(&bytes)
+                .0
+                .into_owned()
+        })
+    });
+
+    c.bench_function("gb2312::decode_to_utf8", |b| {
+        b.iter(|| gb2312::gb2312_decode_to_utf8(black_box(&bytes), &utf8).unwrap())
+    });
 }
 
 fn random_0_benchmark(c: &mut Criterion) {
@@ -0,0 +1,30 @@
+use criterion::{Criterion, black_box, criterion_group, criterion_main};
+
+use Janus::{euc_jp, euc_kr, gb2312, multi, tools};
+use std::fs;
+
+fn multi_benchmark(c: &mut Criterion) {
+    let bytes = fs::read("dream_gb2312.txt").unwrap();
+    let gb2312_table = tools::build_i32_table_from_bool(&gb2312::build_gb2312_table());
+    let kr_table = tools::build_i32_table_from_bool(&euc_kr::build_kr_table());
+    let jp_table = tools::build_i32_table_from_bool(&euc_jp::build_jp_table());
+    let multi_table = multi::build_multi_table();
+
+    c.bench_function("multi::three_passes", |b| {
+        b.iter(|| {
+            let bytes = black_box(&bytes);
+            (
+                gb2312::is_gb2312_simd(bytes, &gb2312_table),
+                euc_kr::is_kr_simd(bytes, &kr_table),
+                euc_jp::is_jp_simd(bytes, &jp_table),
+            )
+        })
+    });
+
+    c.bench_function("multi::multi_valid_mask", |b| {
+        b.iter(|| multi::multi_valid_mask(black_box(&bytes), black_box(&multi_table)))
+    });
+}
+
+criterion_group!(benches, multi_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,31 @@
+use criterion::{Criterion, black_box, criterion_group, criterion_main};
+
+use Janus::utf16;
+use encoding_rs::GBK;
+use std::fs;
+
+fn utf16_benchmark(c: &mut Criterion) {
+    // the GB2312 novel transcoded to UTF-16LE
+    let gb_bytes = fs::read("dream_gb2312.txt").unwrap();
+    let (text, _, _) = GBK.decode(&gb_bytes);
+    let bytes: Vec<u8> = text.encode_utf16().flat_map(|u| u.to_le_bytes()).collect();
+
+    c.bench_function("utf16::is_utf16le_iconv", |b| {
+        b.iter(|| utf16::is_utf16le_iconv(black_box(&bytes)))
+    });
+
+    c.bench_function("utf16::is_utf16le_rs", |b| {
+        b.iter(|| utf16::is_utf16le_rs(black_box(&bytes)))
+    });
+
+    c.bench_function("utf16::is_utf16le_range", |b| {
+        b.iter(|| utf16::is_utf16le_range(black_box(&bytes)))
+    });
+
+    c.bench_function("utf16::is_utf16le_simd", |b| {
+        b.iter(|| utf16::is_utf16le_simd(black_box(&bytes)))
+    });
+}
+
+criterion_group!(benches, utf16_benchmark);
+criterion_main!(benches);
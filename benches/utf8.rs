@@ -0,0 +1,35 @@
+use criterion::{Criterion, black_box, criterion_group, criterion_main};
+
+use Janus::utf8;
+use encoding_rs::GBK;
+use std::fs;
+
+fn utf8_benchmark(c: &mut Criterion) {
+    // the GB2312 novel transcoded to UTF-8
+    let gb_bytes = fs::read("dream_gb2312.txt").unwrap();
+    let (text, _, _) = GBK.decode(&gb_bytes);
+    let bytes = text.as_bytes();
+
+    c.bench_function("utf8::is_utf8_iconv", |b| {
+        b.iter(|| utf8::is_utf8_iconv(black_box(bytes)))
+    });
+
+    c.bench_function("utf8::is_utf8_rs", |b| {
+        b.iter(|| utf8::is_utf8_rs(black_box(bytes)))
+    });
+
+    c.bench_function("utf8::is_utf8_std", |b| {
+        b.iter(|| std::str::from_utf8(black_box(bytes)).is_ok())
+    });
+
+    c.bench_function("utf8::is_utf8_range", |b| {
+        b.iter(|| utf8::is_utf8_range(black_box(bytes)))
+    });
+
+    c.bench_function("utf8::is_utf8_simd", |b| {
+        b.iter(|| utf8::is_utf8_simd(black_box(bytes)))
+    });
+}
+
+criterion_group!(benches, utf8_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,30 @@
+// Regenerates the default models in models/ from the bundled novels.
+// Run from the repository root: cargo run --release --example build_models
+
+use Janus::encoding::Encoding;
+use Janus::model::FrequencyModel;
+use std::fs;
+
+fn main() {
+    let corpora = [
+        ("dream_gb2312.txt", Encoding::Gb2312, "models/gb2312.model"),
+        (
+            "heartless_euckr.txt",
+            Encoding::EucKr,
+            "models/euc_kr.model",
+        ),
+        ("meian_eucjp.txt", Encoding::EucJp, "models/euc_jp.model"),
+    ];
+    for (corpus, encoding, output) in corpora {
+        let bytes = fs::read(corpus).unwrap();
+        let model = FrequencyModel::from_corpus(encoding, &bytes).unwrap();
+        model.save(output).unwrap();
+        println!(
+            "{}: {} characters, {} distinct -> {}",
+            corpus,
+            model.total(),
+            model.len(),
+            output
+        );
+    }
+}
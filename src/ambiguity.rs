@@ -0,0 +1,183 @@
+use crate::encoding::Encoding;
+use crate::{euc_jp, euc_kr, gb2312, tools};
+use std::fmt;
+use std::ops::Range;
+
+// Explains why an input is valid in several of GB2312, EUC-KR and EUC-JP:
+// what each candidate decodes it to, which of those characters are unusual
+// for the repertoire, and where the readings first part ways.
+
+const CANDIDATES: [Encoding; 3] = [Encoding::Gb2312, Encoding::EucKr, Encoding::EucJp];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DecodedChar {
+    pub offset: usize,
+    pub len: usize,
+    pub ch: char,
+    // outside the rows that carry everyday text in the repertoire
+    pub rare: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CandidateReport {
+    pub encoding: Encoding,
+    pub chars: Vec<DecodedChar>,
+}
+
+impl CandidateReport {
+    pub fn text(&self) -> String {
+        self.chars.iter().map(|decoded| decoded.ch).collect()
+    }
+
+    pub fn rare_count(&self) -> usize {
+        self.chars.iter().filter(|decoded| decoded.rare).count()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AmbiguityReport {
+    pub candidates: Vec<CandidateReport>,
+    // smallest span, on a boundary of every candidate, where the candidates
+    // decode to different characters; `None` if they all read the same
+    pub diverging_span: Option<Range<usize>>,
+}
+
+impl AmbiguityReport {
+    pub fn is_ambiguous(&self) -> bool {
+        self.candidates.len() > 1
+    }
+}
+
+fn row_ranges(encoding: Encoding, lead: u8) -> &'static [(u8, u8)] {
+    let row = (lead - 0x80) as usize;
+    match encoding {
+        Encoding::Gb2312 => gb2312::VALID_RANGES[row],
+        Encoding::EucKr => euc_kr::VALID_KR_RANGES[row],
+        _ => euc_jp::VALID_JP_RANGES[row],
+    }
+}
+
+// Symbols, full-width alphanumerics and the main script of each
+// repertoire: level 1 hanzi, Hangul, kana and level 1 kanji.
+fn is_common_row(encoding: Encoding, lead: u8) -> bool {
+    match encoding {
+        Encoding::Gb2312 => matches!(lead, 0xA1 | 0xA3 | 0xB0..=0xD7),
+        Encoding::EucKr => matches!(lead, 0xA1 | 0xA3 | 0xB0..=0xC8),
+        _ => matches!(lead, 0xA1 | 0xA3..=0xA5 | 0xB0..=0xCF),
+    }
+}
+
+// Length of the character at `data[i]` if it is assigned in the row tables.
+fn assigned_len(encoding: Encoding, data: &[u8], i: usize) -> Option<usize> {
+    let lead = data[i];
+    if lead <= 0x7F {
+        return Some(1);
+    }
+    if encoding == Encoding::EucJp && (lead == 0x8E || lead == 0x8F) {
+        return euc_jp::jp_char_len(data, i).ok();
+    }
+    if !(0xA1..=0xFE).contains(&lead) || i + 1 >= data.len() {
+        return None;
+    }
+    let trail = data[i + 1];
+    if (0xA1..=0xFE).contains(&trail) && tools::in_ranges(row_ranges(encoding, lead), trail) {
+        Some(2)
+    } else {
+        None
+    }
+}
+
+fn decode_chars(encoding: Encoding, data: &[u8]) -> Option<Vec<DecodedChar>> {
+    let decoder = encoding.rs_encoding();
+    let mut chars = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        let len = assigned_len(encoding, data, i)?;
+        let (text, _) = decoder.decode_without_bom_handling(&data[i..i + len]);
+        let ch = text.chars().next().unwrap_or(char::REPLACEMENT_CHARACTER);
+        chars.push(DecodedChar {
+            offset: i,
+            len,
+            ch,
+            rare: data[i] >= 0x80 && !is_common_row(encoding, data[i]),
+        });
+        i += len;
+    }
+    Some(chars)
+}
+
+fn diverging_span(candidates: &[CandidateReport]) -> Option<Range<usize>> {
+    let first = candidates.first()?;
+    // first character on which some candidate disagrees with the first one
+    let start = first
+        .chars
+        .iter()
+        .enumerate()
+        .find(|(index, decoded)| {
+            candidates[1..].iter().any(|other| {
+                other.chars.get(*index).map(|o| (o.offset, o.len, o.ch))
+                    != Some((decoded.offset, decoded.len, decoded.ch))
+            })
+        })
+        .map(|(_, decoded)| decoded.offset)?;
+    // extend to the first offset past `start` that ends a character in
+    // every candidate
+    let mut end = start + 1;
+    loop {
+        let mut aligned = true;
+        for candidate in candidates {
+            if let Some(decoded) = candidate
+                .chars
+                .iter()
+                .find(|decoded| decoded.offset < end && decoded.offset + decoded.len > end)
+            {
+                end = decoded.offset + decoded.len;
+                aligned = false;
+            }
+        }
+        if aligned {
+            return Some(start..end);
+        }
+    }
+}
+
+pub fn ambiguity_report(data: &[u8]) -> AmbiguityReport {
+    let candidates: Vec<CandidateReport> = CANDIDATES
+        .iter()
+        .filter_map(|&encoding| {
+            decode_chars(encoding, data).map(|chars| CandidateReport { encoding, chars })
+        })
+        .collect();
+    let diverging_span = if candidates.len() > 1 {
+        diverging_span(&candidates)
+    } else {
+        None
+    };
+    AmbiguityReport {
+        candidates,
+        diverging_span,
+    }
+}
+
+impl fmt::Display for AmbiguityReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.candidates.is_empty() {
+            return writeln!(f, "not valid in GB2312, EUC-KR or EUC-JP");
+        }
+        for candidate in &self.candidates {
+            write!(f, "{}: ", candidate.encoding)?;
+            for decoded in &candidate.chars {
+                if decoded.rare {
+                    write!(f, "[{}]", decoded.ch)?;
+                } else {
+                    write!(f, "{}", decoded.ch)?;
+                }
+            }
+            writeln!(f, " ({} rare)", candidate.rare_count())?;
+        }
+        match &self.diverging_span {
+            Some(span) => writeln!(f, "diverges at bytes {}..{}", span.start, span.end),
+            None => writeln!(f, "no divergence"),
+        }
+    }
+}
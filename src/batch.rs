@@ -0,0 +1,46 @@
+use crate::encoding::Encoding;
+use crate::error::ValidationError;
+
+// Short values are validated one at a time with the SIMD validators.
+// Packing them into one separated buffer for a single sweep costs more in
+// copying, and in sweeping again after each invalid value, than it saves
+// in calls: `benches/batch.rs` compares the two.
+#[derive(Clone, Copy, Debug)]
+pub struct BatchValidator {
+    encoding: Encoding,
+}
+
+impl BatchValidator {
+    pub fn new(encoding: Encoding) -> Self {
+        BatchValidator { encoding }
+    }
+
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    // The first error of each value, with offsets relative to the value.
+    pub fn errors(&self, values: &[&[u8]]) -> Vec<Option<ValidationError>> {
+        values
+            .iter()
+            .map(|value| self.encoding.validate(value).err())
+            .collect()
+    }
+
+    pub fn validate(&self, values: &[&[u8]]) -> Vec<bool> {
+        values
+            .iter()
+            .map(|value| self.encoding.is_valid(value))
+            .collect()
+    }
+}
+
+pub fn validate_batch(values: &[&[u8]], encoding: Encoding) -> Vec<bool> {
+    BatchValidator::new(encoding).validate(values)
+}
+
+// Like `validate_batch`, with the first error of each invalid value;
+// offsets are relative to the value.
+pub fn validate_batch_errors(values: &[&[u8]], encoding: Encoding) -> Vec<Option<ValidationError>> {
+    BatchValidator::new(encoding).errors(values)
+}
@@ -0,0 +1,244 @@
+use crate::encoding::Encoding;
+use std::fmt;
+
+// String columns in the Arrow layout: one values buffer holding the rows
+// back to back, and `rows + 1` offsets where row `i` is
+// `values[offsets[i]..offsets[i + 1]]`.
+//
+// The values buffer is validated in one SIMD sweep. A row is valid when the
+// sweep accepts its bytes and both of its offsets fall on character
+// boundaries, which is the same as validating the row on its own. The
+// sweep restarts at the start of the next row after an invalid row.
+
+// Offset types of Arrow string columns: u32 / i32 for `Utf8`, u64 / i64
+// for `LargeUtf8`.
+pub trait Offset: Copy {
+    // None for negative offsets and offsets that do not fit in memory.
+    fn to_usize(self) -> Option<usize>;
+}
+
+impl Offset for u32 {
+    fn to_usize(self) -> Option<usize> {
+        usize::try_from(self).ok()
+    }
+}
+
+impl Offset for u64 {
+    fn to_usize(self) -> Option<usize> {
+        usize::try_from(self).ok()
+    }
+}
+
+impl Offset for i32 {
+    fn to_usize(self) -> Option<usize> {
+        usize::try_from(self).ok()
+    }
+}
+
+impl Offset for i64 {
+    fn to_usize(self) -> Option<usize> {
+        usize::try_from(self).ok()
+    }
+}
+
+// Offsets that do not describe rows of the values buffer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColumnError {
+    // The offset of `row` is negative or past the end of the values.
+    OutOfBounds { row: usize },
+    // The offset of `row` is less than the offset of the row before it.
+    Decreasing { row: usize },
+}
+
+impl fmt::Display for ColumnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ColumnError::OutOfBounds { row } => {
+                write!(f, "offset {} is out of the values buffer", row)
+            }
+            ColumnError::Decreasing { row } => {
+                write!(f, "offset {} is less than the offset before it", row)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ColumnError {}
+
+// One bit per row, least significant bit first, set for valid rows: the
+// layout of an Arrow validity bitmap. Bits past the last row are zero.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Validity {
+    bits: Vec<u8>,
+    rows: usize,
+}
+
+impl Validity {
+    fn all_valid(rows: usize) -> Self {
+        let mut bits = vec![0xFF; rows.div_ceil(8)];
+        if !rows.is_multiple_of(8) {
+            bits[rows / 8] = (1 << (rows % 8)) - 1;
+        }
+        Validity { bits, rows }
+    }
+
+    fn set_invalid(&mut self, row: usize) {
+        self.bits[row / 8] &= !(1 << (row % 8));
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows == 0
+    }
+
+    pub fn is_valid(&self, row: usize) -> bool {
+        self.bits[row / 8] & (1 << (row % 8)) != 0
+    }
+
+    pub fn invalid_count(&self) -> usize {
+        self.rows
+            - self
+                .bits
+                .iter()
+                .map(|b| b.count_ones() as usize)
+                .sum::<usize>()
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bits
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bits
+    }
+}
+
+fn check_offsets<O: Offset>(values: &[u8], offsets: &[O]) -> Result<Vec<usize>, ColumnError> {
+    let mut checked = Vec::with_capacity(offsets.len());
+    for (row, offset) in offsets.iter().enumerate() {
+        let offset = match offset.to_usize() {
+            Some(offset) if offset <= values.len() => offset,
+            _ => return Err(ColumnError::OutOfBounds { row }),
+        };
+        if checked.last().is_some_and(|&previous| offset < previous) {
+            return Err(ColumnError::Decreasing { row });
+        }
+        checked.push(offset);
+    }
+    Ok(checked)
+}
+
+// Offset of the last ASCII byte in `bytes`, eight bytes at a time: rows of
+// CJK text are mostly long runs of high bytes.
+fn last_ascii(bytes: &[u8]) -> Option<usize> {
+    let mut end = bytes.len();
+    while end >= 8 {
+        let word = u64::from_le_bytes(bytes[end - 8..end].try_into().unwrap());
+        let ascii = !word & 0x8080_8080_8080_8080;
+        if ascii != 0 {
+            return Some(end - 8 + 7 - ascii.leading_zeros() as usize / 8);
+        }
+        end -= 8;
+    }
+    bytes[..end].iter().rposition(|&b| b < 0x80)
+}
+
+// Whether offset `to` starts a character, given that `from` does and that
+// the sweep accepted the characters from `from` through `to`. The scalar
+// walks only cover the bytes since `from`, the previous row boundary.
+fn is_boundary(encoding: Encoding, data: &[u8], from: usize, to: usize) -> bool {
+    if from == to {
+        return true;
+    }
+    let walk = |mut i: usize| {
+        while i < to {
+            match encoding.char_len(data, i) {
+                Ok(len) => i += len,
+                Err(_) => return false,
+            }
+        }
+        i == to
+    };
+    match encoding {
+        // Between ASCII bytes every character has two bytes.
+        Encoding::Gb2312 | Encoding::EucKr => {
+            let anchor = last_ascii(&data[from..to]).map_or(from, |i| from + i + 1);
+            (to - anchor).is_multiple_of(2)
+        }
+        // The same, unless a three-byte 0x8F sequence comes in between.
+        Encoding::EucJp => {
+            let anchor = last_ascii(&data[from..to]).map_or(from, |i| from + i + 1);
+            if data[anchor..to].contains(&0x8F) {
+                walk(anchor)
+            } else {
+                (to - anchor).is_multiple_of(2)
+            }
+        }
+        // Bytes that are never part of a multi-byte character, as in
+        // `parallel::resync`.
+        Encoding::Gb18030 => {
+            let anchor = data[from..to]
+                .iter()
+                .rposition(|&b| matches!(b, 0x00..=0x2F | 0x3A..=0x3F | 0x7F))
+                .map_or(from, |i| from + i + 1);
+            walk(anchor)
+        }
+        Encoding::Utf8 => to == data.len() || data[to] & 0xC0 != 0x80,
+        Encoding::Utf16Le | Encoding::Utf16Be => {
+            let high = if encoding == Encoding::Utf16Le { 1 } else { 0 };
+            (to - from).is_multiple_of(2)
+                && (to + 1 >= data.len() || data[to + high] & 0xFC != 0xDC)
+        }
+    }
+}
+
+// Validates every row of an offsets + values column. `offsets` holds one
+// entry more than there are rows; no offsets at all is an empty column.
+// Bytes outside `offsets[0]..offsets[rows]` are ignored.
+pub fn validate_column<O: Offset>(
+    values: &[u8],
+    offsets: &[O],
+    encoding: Encoding,
+) -> Result<Validity, ColumnError> {
+    let offsets = check_offsets(values, offsets)?;
+    let rows = offsets.len().saturating_sub(1);
+    let mut validity = Validity::all_valid(rows);
+    if rows == 0 {
+        return Ok(validity);
+    }
+    // a character cannot run past the last row
+    let data = &values[..offsets[rows]];
+
+    let mut row = 0;
+    while row < rows {
+        // Sweep from the start of `row`, then accept the rows that end
+        // before the first error on a character boundary.
+        let (stop, failed) = match encoding.validate_span(data, offsets[row], data.len()) {
+            Ok(_) => (data.len(), false),
+            Err(error) => (error.offset, true),
+        };
+        let mut in_step = true;
+        while row < rows && offsets[row + 1] <= stop {
+            let (start, end) = (offsets[row], offsets[row + 1]);
+            // the sweep stopped on a character boundary
+            if end != stop && !is_boundary(encoding, data, start, end) {
+                // the sweep is out of step from here on
+                validity.set_invalid(row);
+                in_step = false;
+            }
+            row += 1;
+            if !in_step {
+                break;
+            }
+        }
+        // the row holding the error
+        if failed && in_step {
+            validity.set_invalid(row);
+            row += 1;
+        }
+    }
+    Ok(validity)
+}
@@ -0,0 +1,186 @@
+use crate::encoding::Encoding;
+use crate::error::{ErrorKind, ValidationError};
+use std::collections::VecDeque;
+use std::fmt;
+use std::ops::Range;
+
+// Decoded characters shown on each side of the offending bytes.
+const CONTEXT_CHARS: usize = 16;
+// Hex dump rows shown before and after the row of the error.
+const DUMP_ROWS: usize = 2;
+const ROW: usize = 16;
+
+// An excerpt of the input around a validation error, for people: where it
+// is in lines and characters, the raw bytes, and the text around it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ErrorContext {
+    pub error: ValidationError,
+    // 1-based; lines end at '\n'
+    pub line: usize,
+    // 1-based, in characters
+    pub column: usize,
+    // the bytes that make up the offending sequence
+    pub bad: Range<usize>,
+    // whole 16-byte rows around the error, starting at `dump_start`
+    pub dump_start: usize,
+    pub dump: Vec<u8>,
+    // decoded characters before and after `bad`, on the same line
+    pub before: String,
+    pub after: String,
+}
+
+fn is_newline(encoding: Encoding, data: &[u8], i: usize) -> bool {
+    match encoding {
+        Encoding::Utf16Le => i + 1 < data.len() && data[i] == b'\n' && data[i + 1] == 0,
+        Encoding::Utf16Be => i + 1 < data.len() && data[i] == 0 && data[i + 1] == b'\n',
+        _ => data[i] == b'\n',
+    }
+}
+
+// The shortest run of bytes at `offset` that `char_len` rejects:
+// `char_len` only reports a truncated character for a valid prefix, so
+// adding bytes until the error changes finds the end.
+fn bad_len(encoding: Encoding, data: &[u8], offset: usize) -> usize {
+    let rest = &data[offset..];
+    (1..rest.len())
+        .find(|&len| encoding.char_len(&rest[..len], 0) != Err(ErrorKind::Truncated))
+        .unwrap_or(rest.len())
+        .max(1)
+}
+
+// Valid characters from `from` up to a newline, a character that does not
+// decode or `CONTEXT_CHARS` characters.
+fn decode_after(encoding: Encoding, data: &[u8], from: usize) -> String {
+    let mut end = from;
+    for _ in 0..CONTEXT_CHARS {
+        if end >= data.len() || is_newline(encoding, data, end) {
+            break;
+        }
+        match encoding.char_len(data, end) {
+            Ok(len) => end += len,
+            Err(_) => break,
+        }
+    }
+    decode(encoding, &data[from..end])
+}
+
+fn decode(encoding: Encoding, data: &[u8]) -> String {
+    let (text, _) = encoding.rs_encoding().decode_without_bom_handling(data);
+    text.into_owned()
+}
+
+// `error` must come from validating `data`, so that the bytes before it are
+// valid. Counting lines and columns is linear in the offset; the rest only
+// looks at a few rows around the error.
+pub fn error_context(data: &[u8], error: &ValidationError) -> ErrorContext {
+    let encoding = error.encoding;
+    let offset = error.offset.min(data.len());
+    let unit = match encoding {
+        Encoding::Utf16Le | Encoding::Utf16Be => 2,
+        _ => 1,
+    };
+
+    let (line, line_start) = if unit == 1 {
+        let before = &data[..offset];
+        (
+            1 + before.iter().filter(|&&b| b == b'\n').count(),
+            before
+                .iter()
+                .rposition(|&b| b == b'\n')
+                .map_or(0, |i| i + 1),
+        )
+    } else {
+        let mut line = 1;
+        let mut line_start = 0;
+        let mut i = 0;
+        while i + unit <= offset {
+            if is_newline(encoding, data, i) {
+                line += 1;
+                line_start = i + unit;
+            }
+            i += unit;
+        }
+        (line, line_start)
+    };
+
+    // step over the characters of the line, keeping the last few starts
+    let mut column = 1;
+    let mut starts = VecDeque::with_capacity(CONTEXT_CHARS + 1);
+    let mut i = line_start;
+    while i < offset {
+        starts.push_back(i);
+        if starts.len() > CONTEXT_CHARS {
+            starts.pop_front();
+        }
+        i += encoding.char_len(data, i).unwrap_or(1);
+        column += 1;
+    }
+    let before_start = starts.front().copied().unwrap_or(offset);
+
+    let bad = if offset < data.len() {
+        offset..offset + bad_len(encoding, data, offset)
+    } else {
+        offset..offset
+    };
+    let dump_start = (offset / ROW).saturating_sub(DUMP_ROWS) * ROW;
+    let dump_end = ((bad.end.max(offset + 1)).div_ceil(ROW) + DUMP_ROWS) * ROW;
+    let dump = data[dump_start.min(data.len())..dump_end.min(data.len())].to_vec();
+
+    ErrorContext {
+        error: *error,
+        line,
+        column,
+        before: decode(encoding, &data[before_start..offset]),
+        after: decode_after(encoding, data, bad.end),
+        bad,
+        dump_start,
+        dump,
+    }
+}
+
+impl fmt::Display for ErrorContext {
+    // Renders like this, with carets under the offending bytes and more
+    // rows of the dump around them:
+    //
+    // invalid GB2312 at byte 1037: invalid trail byte
+    //   --> line 13, column 301
+    // 00000400  d6 d3 d0 a1 ae c3 c0 d6  d0 b2 bb d7 e3 a3 41 ba  |..............A.|
+    //                                                   ^^ ^^
+    //   text: 但不能永远依恃；况又有‘美中不足�好事多磨’八个字紧相连属，瞬息间
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.error)?;
+        writeln!(f, "  --> line {}, column {}", self.line, self.column)?;
+        for (row, bytes) in self.dump.chunks(ROW).enumerate() {
+            let start = self.dump_start + row * ROW;
+            let mut hex = String::new();
+            let mut marks = String::new();
+            for j in 0..ROW {
+                let gap = if j == ROW / 2 { " " } else { "" };
+                let (cell, mark) = match bytes.get(j) {
+                    Some(byte) if self.bad.contains(&(start + j)) => {
+                        (format!("{:02x}", byte), "^^")
+                    }
+                    Some(byte) => (format!("{:02x}", byte), "  "),
+                    None => ("  ".to_string(), "  "),
+                };
+                hex += &format!("{} {}", gap, cell);
+                marks += &format!("{} {}", gap, mark);
+            }
+            let ascii: String = bytes
+                .iter()
+                .map(|&b| {
+                    if b.is_ascii_graphic() || b == b' ' {
+                        b as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect();
+            writeln!(f, "{:08x} {}  |{}|", start, hex, ascii)?;
+            if marks.contains('^') {
+                writeln!(f, "         {}", marks.trim_end())?;
+            }
+        }
+        write!(f, "  text: {}\u{FFFD}{}", self.before, self.after)
+    }
+}
@@ -0,0 +1,74 @@
+use crate::encoding::Encoding;
+use crate::error::ValidationError;
+
+// Candidates advance in lockstep over blocks of this size, so an encoding
+// that fails early stops costing anything.
+const BLOCK: usize = 64 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Candidate {
+    pub encoding: Encoding,
+    // first error, `None` if the input is structurally valid
+    pub error: Option<ValidationError>,
+}
+
+impl Candidate {
+    pub fn is_valid(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+pub fn detect(data: &[u8]) -> Vec<Candidate> {
+    detect_among(data, &Encoding::ALL)
+}
+
+// Valid candidates come first in the given order, followed by the failed
+// ones, longest valid prefix first.
+pub fn detect_among(data: &[u8], encodings: &[Encoding]) -> Vec<Candidate> {
+    let mut candidates: Vec<Candidate> = encodings
+        .iter()
+        .map(|&encoding| Candidate {
+            encoding,
+            error: None,
+        })
+        .collect();
+    let mut positions = vec![0; candidates.len()];
+
+    let mut end = 0;
+    while end < data.len() {
+        end = (end + BLOCK).min(data.len());
+        let mut alive = false;
+        for (candidate, pos) in candidates.iter_mut().zip(positions.iter_mut()) {
+            if candidate.error.is_some() {
+                continue;
+            }
+            if *pos < end {
+                match candidate.encoding.validate_span(data, *pos, end) {
+                    Ok(next) => *pos = next,
+                    Err(error) => {
+                        candidate.error = Some(error);
+                        continue;
+                    }
+                }
+            }
+            alive = true;
+        }
+        if !alive {
+            break;
+        }
+    }
+
+    candidates.sort_by_key(|candidate| match candidate.error {
+        None => (0, 0),
+        Some(error) => (1, usize::MAX - error.offset),
+    });
+    candidates
+}
+
+pub fn valid_encodings(data: &[u8]) -> Vec<Encoding> {
+    detect(data)
+        .into_iter()
+        .filter(Candidate::is_valid)
+        .map(|candidate| candidate.encoding)
+        .collect()
+}
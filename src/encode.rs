@@ -0,0 +1,424 @@
+use crate::encoding::Encoding;
+use crate::error::ValidationError;
+use crate::euc_jp::JpProfile;
+use crate::{euc_jp, euc_kr, gb2312, gb18030, tools};
+use std::fmt;
+use std::sync::OnceLock;
+
+// Encoders from UTF-8 into the legacy encodings. Their repertoires are the
+// validity tables: a character is encoded only as a sequence the
+// validators accept, so the output of `encode` always passes them.
+
+// A character of the input that the target encoding cannot represent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct EncodeError {
+    pub encoding: Encoding,
+    // byte offset of the character in the UTF-8 input
+    pub offset: usize,
+    pub character: char,
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "U+{:04X} {:?} at byte {} is not in {}",
+            self.character as u32, self.character, self.offset, self.encoding
+        )
+    }
+}
+
+impl std::error::Error for EncodeError {}
+
+// Bytes of each BMP code point in one encoding, packed like the entries of
+// `tools::build_utf8_table`: little-endian, with their count in the top
+// byte. Zero for code points outside the repertoire.
+pub(crate) struct Repertoire {
+    bmp: Box<[u32]>,
+    // GB18030 only: `linear index + 1` of the four-byte sequence of each
+    // BMP code point without a two-byte one
+    four_byte: Box<[u16]>,
+    // one bit per BMP code point in the repertoire, for `is_encodable`
+    bits: Box<[u64]>,
+}
+
+impl Repertoire {
+    fn new() -> Self {
+        Repertoire {
+            bmp: vec![0; 0x10000].into_boxed_slice(),
+            four_byte: Box::new([]),
+            bits: Box::new([]),
+        }
+    }
+
+    // Fills in `bits` once the tables are complete.
+    fn finish(mut self) -> Self {
+        let mut bits = vec![0u64; 0x10000 / 64];
+        for code_point in 0..0x10000 {
+            let four_byte = self.four_byte.get(code_point).is_some_and(|&i| i != 0);
+            if code_point < 0x80 || self.bmp[code_point] != 0 || four_byte {
+                bits[code_point / 64] |= 1 << (code_point % 64);
+            }
+        }
+        self.bits = bits.into_boxed_slice();
+        self
+    }
+
+    fn contains(&self, code_point: u32) -> bool {
+        if code_point < 0x10000 {
+            self.bits[code_point as usize / 64] & (1 << (code_point % 64)) != 0
+        } else {
+            // only GB18030 has the supplementary planes
+            !self.four_byte.is_empty()
+        }
+    }
+
+    // Adds the pairs of a table indexed like the validity tables, after
+    // `prefix`. The first sequence of a code point wins, as in encoding_rs.
+    // U+FFFD only shows up for pairs that encoding_rs does not map.
+    fn add_pairs(&mut self, unicode: &[u16; 32768], prefix: &[u8]) {
+        for (index, &code_point) in unicode.iter().enumerate() {
+            if code_point == 0 || code_point == 0xFFFD || self.bmp[code_point as usize] != 0 {
+                continue;
+            }
+            let mut bytes = prefix.to_vec();
+            bytes.extend_from_slice(&[(index / 256) as u8 + 0x80, index as u8]);
+            self.add(code_point, &bytes);
+        }
+    }
+
+    fn add(&mut self, code_point: u16, bytes: &[u8]) {
+        let mut packed = [0; 4];
+        packed[..bytes.len()].copy_from_slice(bytes);
+        self.bmp[code_point as usize] = u32::from_le_bytes(packed) | (bytes.len() as u32) << 24;
+    }
+
+    // The four-byte sequences of GB18030, for the BMP code points without a
+    // two-byte one, and with them the supplementary planes.
+    fn add_four_byte(&mut self) {
+        let mut four_byte = vec![0; 0x10000];
+        for (index, &code_point) in gb18030::build_gb18030_four_byte_table().iter().enumerate() {
+            let code_point = code_point as usize;
+            if code_point != 0 && self.bmp[code_point] == 0 && four_byte[code_point] == 0 {
+                four_byte[code_point] = index as u16 + 1;
+            }
+        }
+        self.four_byte = four_byte.into_boxed_slice();
+    }
+
+    fn add_jp(&mut self, profile: JpProfile) {
+        self.add_pairs(&euc_jp::build_jp_profile_unicode_table(profile), &[]);
+        // half-width katakana
+        for trail in 0xA1..=0xDF {
+            self.add(0xFF61 + (trail - 0xA1) as u16, &[0x8E, trail]);
+        }
+        self.add_pairs(
+            &euc_jp::build_jp_x0212_profile_unicode_table(profile),
+            &[0x8F],
+        );
+    }
+
+    fn push(&self, out: &mut Vec<u8>, c: char) -> bool {
+        let code_point = c as usize;
+        if let Some(&entry) = self.bmp.get(code_point).filter(|&&entry| entry != 0) {
+            out.extend_from_slice(&entry.to_le_bytes()[..(entry >> 24) as usize]);
+            return true;
+        }
+        if self.four_byte.is_empty() {
+            return false;
+        }
+        if code_point >= 0x10000 {
+            out.extend_from_slice(&gb18030::supplementary_sequence(c));
+            return true;
+        }
+        match self.four_byte[code_point] {
+            0 => false,
+            index => {
+                out.extend_from_slice(&gb18030::four_byte_sequence(index as u32 - 1));
+                true
+            }
+        }
+    }
+}
+
+// Legacy character sets a document can be written in: the encodings the
+// validators know and the vendor supersets of each.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Charset {
+    Gb2312,
+    // the two-byte part of GB18030, as encoding_rs writes GBK
+    Gbk,
+    Gb18030,
+    EucKr,
+    // Unified Hangul Code, which encoding_rs reads and writes as EUC-KR
+    Cp949,
+    EucJp,
+    Cp51932,
+    EucJpMs,
+}
+
+impl Charset {
+    pub fn name(self) -> &'static str {
+        match self {
+            Charset::Gb2312 => "GB2312",
+            Charset::Gbk => "GBK",
+            Charset::Gb18030 => "GB18030",
+            Charset::EucKr => "EUC-KR",
+            Charset::Cp949 => "CP949",
+            Charset::EucJp => "EUC-JP",
+            Charset::Cp51932 => "CP51932",
+            Charset::EucJpMs => "eucJP-ms",
+        }
+    }
+
+    // The encoding with this exact repertoire, if the validators have one.
+    pub fn encoding(self) -> Option<Encoding> {
+        match self {
+            Charset::Gb2312 => Some(Encoding::Gb2312),
+            Charset::Gb18030 => Some(Encoding::Gb18030),
+            Charset::EucKr => Some(Encoding::EucKr),
+            Charset::EucJp => Some(Encoding::EucJp),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn repertoire(self) -> &'static Repertoire {
+        REPERTOIRES[self as usize].get_or_init(|| {
+            let mut repertoire = Repertoire::new();
+            match self {
+                Charset::Gb2312 => repertoire.add_pairs(&gb2312::build_gb2312_unicode_table(), &[]),
+                Charset::Gbk => repertoire.add_pairs(&gb18030::build_gb18030_unicode_table(), &[]),
+                Charset::Gb18030 => {
+                    repertoire.add_pairs(&gb18030::build_gb18030_unicode_table(), &[]);
+                    repertoire.add_four_byte();
+                }
+                Charset::EucKr => repertoire.add_pairs(&euc_kr::build_kr_unicode_table(), &[]),
+                Charset::Cp949 => repertoire.add_pairs(&euc_kr::build_cp949_unicode_table(), &[]),
+                Charset::EucJp => repertoire.add_jp(JpProfile::Jis),
+                Charset::Cp51932 => repertoire.add_jp(JpProfile::Cp51932),
+                Charset::EucJpMs => repertoire.add_jp(JpProfile::EucJpMs),
+            }
+            repertoire.finish()
+        })
+    }
+}
+
+impl fmt::Display for Charset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+static REPERTOIRES: [OnceLock<Repertoire>; 8] = [const { OnceLock::new() }; 8];
+
+// None for the Unicode encodings, which have every character.
+pub(crate) fn repertoire(encoding: Encoding) -> Option<&'static Repertoire> {
+    let charset = match encoding {
+        Encoding::Gb2312 => Charset::Gb2312,
+        Encoding::Gb18030 => Charset::Gb18030,
+        Encoding::EucJp => Charset::EucJp,
+        Encoding::EucKr => Charset::EucKr,
+        Encoding::Utf8 | Encoding::Utf16Le | Encoding::Utf16Be => return None,
+    };
+    Some(charset.repertoire())
+}
+
+// Encodes `text`, or reports its first character outside the repertoire of
+// `encoding`. ASCII runs are copied in blocks.
+pub fn encode(text: &str, encoding: Encoding) -> Result<Vec<u8>, EncodeError> {
+    let Some(repertoire) = repertoire(encoding) else {
+        return Ok(match encoding {
+            Encoding::Utf16Le => text.encode_utf16().flat_map(u16::to_le_bytes).collect(),
+            Encoding::Utf16Be => text.encode_utf16().flat_map(u16::to_be_bytes).collect(),
+            _ => text.as_bytes().to_vec(),
+        });
+    };
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] < 0x80 {
+            let len = tools::ascii_prefix(&bytes[i..]);
+            out.extend_from_slice(&bytes[i..i + len]);
+            i += len;
+            continue;
+        }
+        let c = text[i..].chars().next().unwrap();
+        if !repertoire.push(&mut out, c) {
+            return Err(EncodeError {
+                encoding,
+                offset: i,
+                character: c,
+            });
+        }
+        i += c.len_utf8();
+    }
+    Ok(out)
+}
+
+// Code point and length of the non-ASCII character at `bytes[i]`, where
+// `bytes` is valid UTF-8.
+fn code_point_at(bytes: &[u8], i: usize) -> (u32, usize) {
+    let lead = bytes[i] as u32;
+    let trail = |j: usize| (bytes[i + j] & 0x3F) as u32;
+    match lead {
+        0xC0..=0xDF => ((lead & 0x1F) << 6 | trail(1), 2),
+        0xE0..=0xEF => ((lead & 0x0F) << 12 | trail(1) << 6 | trail(2), 3),
+        _ => (
+            (lead & 0x07) << 18 | trail(1) << 12 | trail(2) << 6 | trail(3),
+            4,
+        ),
+    }
+}
+
+// Offset and code point of the first character of `bytes[from..]` outside
+// the repertoire. ASCII runs are skipped sixteen bytes at a time; every
+// other character is one bit test.
+fn next_unencodable(bytes: &[u8], from: usize, repertoire: &Repertoire) -> Option<(usize, u32)> {
+    let mut i = from;
+    while i < bytes.len() {
+        if bytes[i] < 0x80 {
+            i += tools::ascii_prefix(&bytes[i..]);
+            continue;
+        }
+        let (code_point, len) = code_point_at(bytes, i);
+        if !repertoire.contains(code_point) {
+            return Some((i, code_point));
+        }
+        i += len;
+    }
+    None
+}
+
+// Whether `encode(text, encoding)` would succeed, without encoding.
+pub fn is_encodable(text: &str, encoding: Encoding) -> bool {
+    repertoire(encoding)
+        .is_none_or(|repertoire| next_unencodable(text.as_bytes(), 0, repertoire).is_none())
+}
+
+// Every character of `text` that `encoding` cannot represent, in order,
+// repeats included.
+pub fn find_unencodable(text: &str, encoding: Encoding) -> Vec<EncodeError> {
+    let mut errors = Vec::new();
+    let Some(repertoire) = repertoire(encoding) else {
+        return errors;
+    };
+    let mut from = 0;
+    while let Some((offset, code_point)) = next_unencodable(text.as_bytes(), from, repertoire) {
+        let character = char::from_u32(code_point).unwrap();
+        errors.push(EncodeError {
+            encoding,
+            offset,
+            character,
+        });
+        from = offset + character.len_utf8();
+    }
+    errors
+}
+
+// Why the output of an encoder does not stand for its input.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RoundtripError {
+    // the output is not valid in the encoding
+    Invalid(ValidationError),
+    // the output decodes to other text; `offset` is the first byte of the
+    // input that differs
+    Mismatch { offset: usize },
+}
+
+impl fmt::Display for RoundtripError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RoundtripError::Invalid(error) => write!(f, "encoded text is {}", error),
+            RoundtripError::Mismatch { offset } => {
+                write!(f, "encoded text decodes differently from byte {}", offset)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RoundtripError {}
+
+// Checks that `encoded` is valid in `encoding`, with the SIMD validators,
+// and that encoding_rs decodes it back to `text`. The decoder is not this
+// crate's, so a wrong entry in the tables shows up here.
+pub fn roundtrip_check(
+    text: &str,
+    encoded: &[u8],
+    encoding: Encoding,
+) -> Result<(), RoundtripError> {
+    if !encoding.is_valid(encoded) {
+        return Err(RoundtripError::Invalid(
+            encoding.validate(encoded).unwrap_err(),
+        ));
+    }
+    let (decoded, _) = encoding.rs_encoding().decode_without_bom_handling(encoded);
+    if decoded != text {
+        let mut offset = text
+            .bytes()
+            .zip(decoded.bytes())
+            .position(|(a, b)| a != b)
+            .unwrap_or(text.len().min(decoded.len()));
+        while !text.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        return Err(RoundtripError::Mismatch { offset });
+    }
+    Ok(())
+}
+
+// Orders of preference for `best_legacy_encoding`, narrowest first. Each
+// is a superset of the ones before it, except that CP51932 gives up the
+// JIS X 0212 plane of EUC-JP for the NEC and IBM extensions; eucJP-ms has
+// both.
+pub const CHINESE: [Charset; 3] = [Charset::Gb2312, Charset::Gbk, Charset::Gb18030];
+pub const KOREAN: [Charset; 2] = [Charset::EucKr, Charset::Cp949];
+pub const JAPANESE: [Charset; 3] = [Charset::EucJp, Charset::Cp51932, Charset::EucJpMs];
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LegacyChoice {
+    // the first of the preferences that represents all of the text
+    Fits(Charset),
+    // none does: the share of the characters of the text that each one
+    // represents, in percent, in the order of the preferences
+    Coverage(Vec<(Charset, f64)>),
+}
+
+// Share of the characters of `text` in the repertoire of `charset`, in
+// percent; 100 for empty text.
+pub fn coverage(text: &str, charset: Charset) -> f64 {
+    let repertoire = charset.repertoire();
+    let bytes = text.as_bytes();
+    let mut missing = 0;
+    let mut from = 0;
+    while let Some((offset, code_point)) = next_unencodable(bytes, from, repertoire) {
+        missing += 1;
+        from = offset + char::from_u32(code_point).unwrap().len_utf8();
+    }
+    if missing == 0 {
+        return 100.0;
+    }
+    let chars = text.chars().count();
+    (chars - missing) as f64 * 100.0 / chars as f64
+}
+
+// Picks the charset to write `text` in: the first of `preferences` that
+// represents all of it, such as GB2312 before GBK before GB18030
+// (`CHINESE`). Otherwise reports how much of the text each one covers.
+// The choice only names the charset: `encode` writes the ones with an
+// `Encoding`, and encoding_rs's GBK, EUC-KR and EUC-JP encoders write GBK,
+// CP949 and CP51932. Nothing here writes eucJP-ms.
+pub fn best_legacy_encoding(text: &str, preferences: &[Charset]) -> LegacyChoice {
+    let bytes = text.as_bytes();
+    for &charset in preferences {
+        if next_unencodable(bytes, 0, charset.repertoire()).is_none() {
+            return LegacyChoice::Fits(charset);
+        }
+    }
+    LegacyChoice::Coverage(
+        preferences
+            .iter()
+            .map(|&charset| (charset, coverage(text, charset)))
+            .collect(),
+    )
+}
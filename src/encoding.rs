@@ -0,0 +1,311 @@
+use crate::error::{ErrorKind, ValidationError};
+use crate::tools::Utf8Table;
+use crate::{euc_jp, euc_kr, gb2312, gb18030, tools, utf8, utf16};
+use std::fmt;
+use std::sync::OnceLock;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Encoding {
+    Gb2312,
+    Gb18030,
+    EucJp,
+    // plain KS X 1001; the composition sequences, user-defined rows and
+    // KPS 9566 of `euc_kr::KrOptions` need the `euc_kr::*_with` validators
+    EucKr,
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+}
+
+static GB2312_TABLE: OnceLock<Box<[i32; 32768]>> = OnceLock::new();
+static JP_TABLE: OnceLock<Box<[i32; 32768]>> = OnceLock::new();
+static KR_TABLE: OnceLock<Box<[i32; 32768]>> = OnceLock::new();
+
+pub(crate) fn gb2312_table() -> &'static [i32; 32768] {
+    GB2312_TABLE.get_or_init(|| {
+        Box::new(tools::build_i32_table_from_bool(
+            &gb2312::build_gb2312_table(),
+        ))
+    })
+}
+
+pub(crate) fn jp_table() -> &'static [i32; 32768] {
+    JP_TABLE.get_or_init(|| Box::new(tools::build_i32_table_from_bool(&euc_jp::build_jp_table())))
+}
+
+pub(crate) fn kr_table() -> &'static [i32; 32768] {
+    KR_TABLE.get_or_init(|| Box::new(tools::build_i32_table_from_bool(&euc_kr::build_kr_table())))
+}
+
+static GB2312_LOOKUP: OnceLock<Box<[bool; 32768]>> = OnceLock::new();
+static JP_LOOKUP: OnceLock<Box<[bool; 32768]>> = OnceLock::new();
+static KR_LOOKUP: OnceLock<Box<[bool; 32768]>> = OnceLock::new();
+
+// Unicode mapping tables of the fused decoders, beside the validity tables,
+// with each character already in UTF-8 (`tools::build_utf8_table`).
+static GB2312_UTF8: OnceLock<Utf8Table> = OnceLock::new();
+static GB18030_UTF8: OnceLock<(Utf8Table, Vec<u16>)> = OnceLock::new();
+static JP_UTF8: OnceLock<(Utf8Table, Utf8Table)> = OnceLock::new();
+static KR_UTF8: OnceLock<Utf8Table> = OnceLock::new();
+
+fn utf8_table(unicode: [u16; 32768]) -> Utf8Table {
+    tools::build_utf8_table(&unicode)
+}
+
+// The validator implementations of each module.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Strategy {
+    Simd,
+    // a table of valid pairs; GB2312, EUC-JP and EUC-KR only, the other
+    // encodings run their scalar validator instead
+    Lookup,
+    // Range and Fsm both name the scalar validator: an FSM for GB18030,
+    // range checks for the others
+    Range,
+    Fsm,
+    Iconv,
+    // encoding_rs, which rejects empty input
+    Rs,
+}
+
+impl Strategy {
+    pub const ALL: [Strategy; 6] = [
+        Strategy::Simd,
+        Strategy::Lookup,
+        Strategy::Range,
+        Strategy::Fsm,
+        Strategy::Iconv,
+        Strategy::Rs,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Strategy::Simd => "simd",
+            Strategy::Lookup => "lookup",
+            Strategy::Range => "range",
+            Strategy::Fsm => "fsm",
+            Strategy::Iconv => "iconv",
+            Strategy::Rs => "rs",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Strategy> {
+        Strategy::ALL
+            .into_iter()
+            .find(|strategy| strategy.name().eq_ignore_ascii_case(name))
+    }
+}
+
+impl fmt::Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl Encoding {
+    pub const ALL: [Encoding; 7] = [
+        Encoding::Gb2312,
+        Encoding::Gb18030,
+        Encoding::EucJp,
+        Encoding::EucKr,
+        Encoding::Utf8,
+        Encoding::Utf16Le,
+        Encoding::Utf16Be,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Encoding::Gb2312 => "GB2312",
+            Encoding::Gb18030 => "GB18030",
+            Encoding::EucJp => "EUC-JP",
+            Encoding::EucKr => "EUC-KR",
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Utf16Le => "UTF-16LE",
+            Encoding::Utf16Be => "UTF-16BE",
+        }
+    }
+
+    // Accepts the names above case-insensitively, with or without '-' or '_'.
+    pub fn from_name(name: &str) -> Option<Encoding> {
+        let key: String = name
+            .chars()
+            .filter(|c| *c != '-' && *c != '_')
+            .map(|c| c.to_ascii_lowercase())
+            .collect();
+        Encoding::ALL.into_iter().find(|encoding| {
+            encoding
+                .name()
+                .chars()
+                .filter(|c| *c != '-')
+                .map(|c| c.to_ascii_lowercase())
+                .eq(key.chars())
+        })
+    }
+
+    // The encoding_rs counterpart; GB2312 maps to its superset GBK.
+    pub fn rs_encoding(self) -> &'static encoding_rs::Encoding {
+        match self {
+            Encoding::Gb2312 => encoding_rs::GBK,
+            Encoding::Gb18030 => encoding_rs::GB18030,
+            Encoding::EucJp => encoding_rs::EUC_JP,
+            Encoding::EucKr => encoding_rs::EUC_KR,
+            Encoding::Utf8 => encoding_rs::UTF_8,
+            Encoding::Utf16Le => encoding_rs::UTF_16LE,
+            Encoding::Utf16Be => encoding_rs::UTF_16BE,
+        }
+    }
+
+    // Length of the character starting at `data[i]`.
+    pub fn char_len(self, data: &[u8], i: usize) -> Result<usize, ErrorKind> {
+        match self {
+            Encoding::Gb2312 => gb2312::gb2312_char_len(data, i),
+            Encoding::Gb18030 => gb18030::gb18030_char_len(data, i),
+            Encoding::EucJp => euc_jp::jp_char_len(data, i),
+            Encoding::EucKr => euc_kr::kr_char_len(data, i),
+            Encoding::Utf8 => utf8::utf8_char_len(data, i),
+            Encoding::Utf16Le => utf16::utf16le_char_len(data, i),
+            Encoding::Utf16Be => utf16::utf16be_char_len(data, i),
+        }
+    }
+
+    // Length of the prefix accepted by the SIMD kernel, a character boundary.
+    pub fn valid_up_to(self, data: &[u8]) -> usize {
+        match self {
+            Encoding::Gb2312 => gb2312::gb2312_valid_up_to(data, gb2312_table()),
+            Encoding::Gb18030 => gb18030::gb18030_valid_up_to(data),
+            Encoding::EucJp => euc_jp::jp_valid_up_to(data, jp_table()),
+            Encoding::EucKr => euc_kr::kr_valid_up_to(data, kr_table()),
+            Encoding::Utf8 => utf8::utf8_valid_up_to(data),
+            Encoding::Utf16Le => utf16::utf16le_valid_up_to(data),
+            Encoding::Utf16Be => utf16::utf16be_valid_up_to(data),
+        }
+    }
+
+    pub fn is_valid(self, data: &[u8]) -> bool {
+        match self {
+            Encoding::Gb2312 => gb2312::is_gb2312_simd(data, gb2312_table()),
+            Encoding::Gb18030 => gb18030::is_gb18030_simd(data),
+            Encoding::EucJp => euc_jp::is_jp_simd(data, jp_table()),
+            Encoding::EucKr => euc_kr::is_kr_simd(data, kr_table()),
+            Encoding::Utf8 => utf8::is_utf8_simd(data),
+            Encoding::Utf16Le => utf16::is_utf16le_simd(data),
+            Encoding::Utf16Be => utf16::is_utf16be_simd(data),
+        }
+    }
+
+    // Runs the validator of the given strategy. Range and Fsm both name the
+    // scalar validator (an FSM for GB18030, range checks otherwise), and
+    // encodings without a lookup table use it for Lookup as well; see
+    // `Strategy`.
+    pub fn is_valid_with(self, data: &[u8], strategy: Strategy) -> bool {
+        match (self, strategy) {
+            (_, Strategy::Simd) => self.is_valid(data),
+            (Encoding::Gb2312, Strategy::Lookup) => gb2312::is_gb2312_lookup(
+                data,
+                GB2312_LOOKUP.get_or_init(|| Box::new(gb2312::build_gb2312_table())),
+            ),
+            (Encoding::EucJp, Strategy::Lookup) => euc_jp::is_jp_lookup(
+                data,
+                JP_LOOKUP.get_or_init(|| Box::new(euc_jp::build_jp_table())),
+            ),
+            (Encoding::EucKr, Strategy::Lookup) => euc_kr::is_kr_lookup(
+                data,
+                KR_LOOKUP.get_or_init(|| Box::new(euc_kr::build_kr_table())),
+            ),
+            (Encoding::Gb2312, Strategy::Iconv) => gb2312::is_gb2312_iconv(data),
+            (Encoding::Gb2312, Strategy::Rs) => gb2312::is_gb2312_rs(data),
+            (Encoding::Gb2312, _) => gb2312::is_gb2312_range(data),
+            (Encoding::Gb18030, Strategy::Iconv) => gb18030::is_gb18030_iconv(data),
+            (Encoding::Gb18030, Strategy::Rs) => gb18030::is_gb18030_rs(data),
+            (Encoding::Gb18030, _) => gb18030::is_gb18030_fsm(data),
+            (Encoding::EucJp, Strategy::Iconv) => euc_jp::is_jp_iconv(data),
+            (Encoding::EucJp, Strategy::Rs) => euc_jp::is_jp_rs(data),
+            (Encoding::EucJp, _) => euc_jp::is_jp_range(data),
+            (Encoding::EucKr, Strategy::Iconv) => euc_kr::is_kr_iconv(data),
+            (Encoding::EucKr, Strategy::Rs) => euc_kr::is_kr_rs(data),
+            (Encoding::EucKr, _) => euc_kr::is_kr_range(data),
+            (Encoding::Utf8, Strategy::Iconv) => utf8::is_utf8_iconv(data),
+            (Encoding::Utf8, Strategy::Rs) => utf8::is_utf8_rs(data),
+            (Encoding::Utf8, _) => utf8::is_utf8_range(data),
+            (Encoding::Utf16Le, Strategy::Iconv) => utf16::is_utf16le_iconv(data),
+            (Encoding::Utf16Le, Strategy::Rs) => utf16::is_utf16le_rs(data),
+            (Encoding::Utf16Le, _) => utf16::is_utf16le_range(data),
+            (Encoding::Utf16Be, Strategy::Iconv) => utf16::is_utf16be_iconv(data),
+            (Encoding::Utf16Be, Strategy::Rs) => utf16::is_utf16be_rs(data),
+            (Encoding::Utf16Be, _) => utf16::is_utf16be_range(data),
+        }
+    }
+
+    pub fn validate(self, data: &[u8]) -> Result<(), ValidationError> {
+        self.validate_span(data, 0, data.len()).map(|_| ())
+    }
+
+    // Validates and decodes in one pass, with the same errors as `validate`
+    // and, for valid input, the same output as `rs_encoding`.
+    pub fn decode_to_utf8(self, data: &[u8]) -> Result<String, ValidationError> {
+        match self {
+            Encoding::Gb2312 => gb2312::gb2312_decode_to_utf8(
+                data,
+                GB2312_UTF8.get_or_init(|| utf8_table(gb2312::build_gb2312_unicode_table())),
+            ),
+            Encoding::Gb18030 => {
+                let (two_byte, four_byte) = GB18030_UTF8.get_or_init(|| {
+                    (
+                        utf8_table(gb18030::build_gb18030_unicode_table()),
+                        gb18030::build_gb18030_four_byte_table(),
+                    )
+                });
+                gb18030::gb18030_decode_to_utf8(data, two_byte, four_byte)
+            }
+            Encoding::EucJp => {
+                let (x0208, x0212) = JP_UTF8.get_or_init(|| {
+                    (
+                        utf8_table(euc_jp::build_jp_unicode_table()),
+                        utf8_table(euc_jp::build_jp_x0212_unicode_table()),
+                    )
+                });
+                euc_jp::jp_decode_to_utf8(data, x0208, x0212)
+            }
+            Encoding::EucKr => euc_kr::kr_decode_to_utf8(
+                data,
+                KR_UTF8.get_or_init(|| utf8_table(euc_kr::build_kr_unicode_table())),
+            ),
+            Encoding::Utf8 | Encoding::Utf16Le | Encoding::Utf16Be => {
+                self.validate(data)?;
+                let (text, _) = self.rs_encoding().decode_without_bom_handling(data);
+                Ok(text.into_owned())
+            }
+        }
+    }
+
+    // Validates the characters starting in `data[start..end]`, where `start`
+    // is a character boundary. The last character may run past `end`; the
+    // returned offset is the end of that character.
+    pub fn validate_span(
+        self,
+        data: &[u8],
+        start: usize,
+        end: usize,
+    ) -> Result<usize, ValidationError> {
+        let mut i = start + self.valid_up_to(&data[start..end]);
+        while i < end {
+            match self.char_len(data, i) {
+                Ok(len) => i += len,
+                Err(kind) => {
+                    return Err(ValidationError {
+                        encoding: self,
+                        offset: i,
+                        kind,
+                    });
+                }
+            }
+        }
+        Ok(i)
+    }
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
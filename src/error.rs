@@ -0,0 +1,64 @@
+use crate::encoding::Encoding;
+use std::fmt;
+use std::io;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    // the input ends inside a character
+    Truncated,
+    InvalidLead,
+    InvalidTrail,
+    // structurally well-formed, but not assigned in the repertoire
+    Unassigned,
+    // GB18030 four-byte sequence or UTF-8 code point beyond the valid ranges
+    OutOfRange,
+    Overlong,
+    // UTF-8 encoded surrogate or unpaired UTF-16 surrogate
+    Surrogate,
+}
+
+impl ErrorKind {
+    pub fn description(self) -> &'static str {
+        match self {
+            ErrorKind::Truncated => "truncated character",
+            ErrorKind::InvalidLead => "invalid lead byte",
+            ErrorKind::InvalidTrail => "invalid trail byte",
+            ErrorKind::Unassigned => "unassigned code point",
+            ErrorKind::OutOfRange => "code point out of range",
+            ErrorKind::Overlong => "overlong sequence",
+            ErrorKind::Surrogate => "invalid surrogate",
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.description())
+    }
+}
+
+// `offset` is the start of the offending character
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ValidationError {
+    pub encoding: Encoding,
+    pub offset: usize,
+    pub kind: ErrorKind,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid {} at byte {}: {}",
+            self.encoding, self.offset, self.kind
+        )
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+impl From<ValidationError> for io::Error {
+    fn from(error: ValidationError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, error)
+    }
+}
@@ -1,3 +1,5 @@
+use crate::encoding::Encoding;
+use crate::error::{ErrorKind, ValidationError};
 use crate::tools;
 use encoding_rs::EUC_JP;
 use std::arch::x86_64::*;
@@ -9,7 +11,23 @@ pub fn is_jp_rs(data: &[u8]) -> bool {
     tools::is_encoding_rs(data, EUC_JP)
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JpProfile {
+    // JIS X 0208 and JIS X 0212 only
+    Jis,
+    // Microsoft CP51932: NEC row 13 and the NEC-selected IBM extensions
+    // (rows 89-92), without the JIS X 0212 plane
+    Cp51932,
+    // eucJP-ms: NEC row 13, the IBM extensions in JIS X 0212 rows 83-84 and
+    // the user-defined rows 85-94 of both planes
+    EucJpMs,
+}
+
 pub fn is_jp_range(data: &[u8]) -> bool {
+    is_jp_range_profile(data, JpProfile::Jis)
+}
+
+pub fn is_jp_range_profile(data: &[u8], profile: JpProfile) -> bool {
     let mut i = 0;
     while i < data.len() {
         let lead = data[i];
@@ -18,7 +36,7 @@ pub fn is_jp_range(data: &[u8]) -> bool {
             i += 1;
         } else if lead == 0x8E {
             // half-width katakana
-            if i + 1 > data.len() {
+            if i + 1 >= data.len() {
                 return false;
             }
             let trail = data[i + 1];
@@ -31,97 +49,19 @@ pub fn is_jp_range(data: &[u8]) -> bool {
             if i + 2 >= data.len() {
                 return false;
             }
-            let second = data[i + 1];
-            let third = data[i + 2];
-
-            if !(0xA1..=0xFE).contains(&second) {
-                return false;
-            }
-            if !(0xA1..=0xFE).contains(&third) {
+            if !is_x0212(data[i + 1], data[i + 2], profile) {
                 return false;
             }
-
-            if second == 0xA1
-                || (0xA3..=0xA5).contains(&second)
-                || second == 0xA8
-                || (0xAC..=0xAF).contains(&second)
-                || (0xEE..=0xFE).contains(&second)
-            {
-                return false;
-            }
-
-            match (second, third) {
-                (0xA2, 0xA1..=0xAE)
-                | (0xA2, 0xBA..=0xC1)
-                | (0xA2, 0xC5..=0xEA)
-                | (0xA2, 0xF2..=0xFE)
-                | (0xA6, 0xA1..=0xE0)
-                | (0xA6, 0xE6)
-                | (0xA6, 0xE8)
-                | (0xA6, 0xEB)
-                | (0xA6, 0xED..=0xF0)
-                | (0xA6, 0xFD..=0xFE)
-                | (0xA7, 0xA1..=0xC1)
-                | (0xA7, 0xCF..=0xF1)
-                | (0xA9, 0xA3)
-                | (0xA9, 0xA5)
-                | (0xA9, 0xA7)
-                | (0xA9, 0xAA)
-                | (0xA9, 0xAE)
-                | (0xA9, 0xB1..=0xC0)
-                | (0xA9, 0xD1..=0xFE)
-                | (0xAA, 0xB9)
-                | (0xAA, 0xF8..=0xFE)
-                | (0xAB, 0xBC)
-                | (0xAB, 0xC4)
-                | (0xAB, 0xF8..=0xFE)
-                | (0xED, 0xE5..=0xFE) => {
-                    return false;
-                }
-                _ => {
-                    i += 3;
-                }
-            }
+            i += 3;
         } else if (0xA1..=0xFE).contains(&lead) {
             // JIS X 0208
             if i + 1 >= data.len() {
                 return false;
             }
-            let trail = data[i + 1];
-            if !(0xA1..=0xFE).contains(&trail) {
-                return false;
-            }
-            if (0xA9..=0xAF).contains(&lead) {
+            if !is_x0208(lead, data[i + 1], profile) {
                 return false;
             }
-            if (0xF5..=0xFE).contains(&lead) {
-                return false;
-            }
-            match (lead, trail) {
-                (0xA2, 0xAF..=0xB9)
-                | (0xA2, 0xC2..=0xC9)
-                | (0xA2, 0xD1..=0xDB)
-                | (0xA2, 0xEB..=0xF1)
-                | (0xA2, 0xFA..=0xFD)
-                | (0xA3, 0xA1..=0xAF)
-                | (0xA3, 0xBA..=0xC0)
-                | (0xA3, 0xDB..=0xE0)
-                | (0xA3, 0xFB..=0xFE)
-                | (0xA4, 0xF4..=0xFE)
-                | (0xA5, 0xF7..=0xFE)
-                | (0xA6, 0xB9..=0xC0)
-                | (0xA6, 0xD9..=0xFE)
-                | (0xA7, 0xC2..=0xD0)
-                | (0xA7, 0xF2..=0xFE)
-                | (0xA8, 0xC1..=0xFE)
-                | (0xCF, 0xD4..=0xFE)
-                | (0xF4, 0xA7..=0xFE) => {
-                    return false;
-                }
-                _ => {
-                    i += 2;
-                }
-            }
+            i += 2;
         } else {
             return false;
         }
@@ -129,7 +69,101 @@ pub fn is_jp_range(data: &[u8]) -> bool {
     true
 }
 
-static VALID_JP_RANGES: [&[(u8, u8)]; 128] = [
+fn is_x0208(lead: u8, trail: u8, profile: JpProfile) -> bool {
+    if !(0xA1..=0xFE).contains(&trail) {
+        return false;
+    }
+    if let Some(ranges) = vendor_row(lead, profile) {
+        return tools::in_ranges(ranges, trail);
+    }
+    if (0xA9..=0xAF).contains(&lead) {
+        return false;
+    }
+    if (0xF5..=0xFE).contains(&lead) {
+        return false;
+    }
+    !matches!(
+        (lead, trail),
+        (0xA2, 0xAF..=0xB9)
+            | (0xA2, 0xC2..=0xC9)
+            | (0xA2, 0xD1..=0xDB)
+            | (0xA2, 0xEB..=0xF1)
+            | (0xA2, 0xFA..=0xFD)
+            | (0xA3, 0xA1..=0xAF)
+            | (0xA3, 0xBA..=0xC0)
+            | (0xA3, 0xDB..=0xE0)
+            | (0xA3, 0xFB..=0xFE)
+            | (0xA4, 0xF4..=0xFE)
+            | (0xA5, 0xF7..=0xFE)
+            | (0xA6, 0xB9..=0xC0)
+            | (0xA6, 0xD9..=0xFE)
+            | (0xA7, 0xC2..=0xD0)
+            | (0xA7, 0xF2..=0xFE)
+            | (0xA8, 0xC1..=0xFE)
+            | (0xCF, 0xD4..=0xFE)
+            | (0xF4, 0xA7..=0xFE)
+    )
+}
+
+fn is_x0212(second: u8, third: u8, profile: JpProfile) -> bool {
+    if !(0xA1..=0xFE).contains(&second) {
+        return false;
+    }
+    if !(0xA1..=0xFE).contains(&third) {
+        return false;
+    }
+
+    match profile {
+        JpProfile::Jis => {}
+        // CP51932 has no three-byte sequences
+        JpProfile::Cp51932 => return false,
+        JpProfile::EucJpMs => {
+            if (second == 0xF3 && third >= 0xF3) || (0xF4..=0xFE).contains(&second) {
+                return true;
+            }
+        }
+    }
+
+    if second == 0xA1
+        || (0xA3..=0xA5).contains(&second)
+        || second == 0xA8
+        || (0xAC..=0xAF).contains(&second)
+        || (0xEE..=0xFE).contains(&second)
+    {
+        return false;
+    }
+
+    !matches!(
+        (second, third),
+        (0xA2, 0xA1..=0xAE)
+            | (0xA2, 0xBA..=0xC1)
+            | (0xA2, 0xC5..=0xEA)
+            | (0xA2, 0xF2..=0xFE)
+            | (0xA6, 0xA1..=0xE0)
+            | (0xA6, 0xE6)
+            | (0xA6, 0xE8)
+            | (0xA6, 0xEB)
+            | (0xA6, 0xED..=0xF0)
+            | (0xA6, 0xFD..=0xFE)
+            | (0xA7, 0xA1..=0xC1)
+            | (0xA7, 0xCF..=0xF1)
+            | (0xA9, 0xA3)
+            | (0xA9, 0xA5)
+            | (0xA9, 0xA7)
+            | (0xA9, 0xAA)
+            | (0xA9, 0xAE)
+            | (0xA9, 0xB1..=0xC0)
+            | (0xA9, 0xD1..=0xFE)
+            | (0xAA, 0xB9)
+            | (0xAA, 0xF8..=0xFE)
+            | (0xAB, 0xBC)
+            | (0xAB, 0xC4)
+            | (0xAB, 0xF8..=0xFE)
+            | (0xED, 0xE4..=0xFE)
+    )
+}
+
+pub(crate) static VALID_JP_RANGES: [&[(u8, u8)]; 128] = [
     // 0x80 (index 0)
     &[],
     // 0x81 (index 1)
@@ -159,7 +193,7 @@ static VALID_JP_RANGES: [&[(u8, u8)]; 128] = [
     // 0x8D (index 13)
     &[],
     // 0x8E (index 14)
-    &[],
+    &[(0xA1, 0xDF)],
     // 0x8F (index 15)
     &[],
     // 0x90 (index 16)
@@ -395,11 +429,49 @@ static VALID_JP_RANGES: [&[(u8, u8)]; 128] = [
     &[],
 ];
 
+// NEC special characters (row 13): circled numbers, Roman numerals, units
+static NEC_ROW_13: &[(u8, u8)] = &[(0xA1, 0xBE), (0xC0, 0xD6), (0xDF, 0xFC)];
+
+// NEC-selected IBM extensions (rows 89-92)
+static NEC_IBM_ROWS: [&[(u8, u8)]; 4] = [
+    &[(0xA1, 0xFE)],
+    &[(0xA1, 0xFE)],
+    &[(0xA1, 0xFE)],
+    &[(0xA1, 0xEE), (0xF1, 0xFE)],
+];
+
+// user-defined area (rows 85-94)
+static USER_DEFINED_ROW: &[(u8, u8)] = &[(0xA1, 0xFE)];
+
+fn vendor_row(lead: u8, profile: JpProfile) -> Option<&'static [(u8, u8)]> {
+    match (profile, lead) {
+        (JpProfile::Jis, _) => None,
+        (_, 0xAD) => Some(NEC_ROW_13),
+        (JpProfile::Cp51932, 0xF9..=0xFC) => Some(NEC_IBM_ROWS[(lead - 0xF9) as usize]),
+        (JpProfile::EucJpMs, 0xF5..=0xFE) => Some(USER_DEFINED_ROW),
+        _ => None,
+    }
+}
+
 pub fn build_jp_table() -> [bool; 32768] {
     tools::build_table(&VALID_JP_RANGES)
 }
 
+pub fn build_jp_profile_table(profile: JpProfile) -> [bool; 32768] {
+    let mut ranges = VALID_JP_RANGES;
+    for lead in 0x80u8..=0xFF {
+        if let Some(row) = vendor_row(lead, profile) {
+            ranges[(lead - 0x80) as usize] = row;
+        }
+    }
+    tools::build_table(&ranges)
+}
+
 pub fn is_jp_lookup(data: &[u8], table: &[bool; 32768]) -> bool {
+    is_jp_lookup_profile(data, table, JpProfile::Jis)
+}
+
+pub fn is_jp_lookup_profile(data: &[u8], table: &[bool; 32768], profile: JpProfile) -> bool {
     let mut i = 0;
     while i < data.len() {
         let lead = data[i];
@@ -411,57 +483,10 @@ pub fn is_jp_lookup(data: &[u8], table: &[bool; 32768]) -> bool {
             if i + 2 >= data.len() {
                 return false;
             }
-            let second = data[i + 1];
-            let third = data[i + 2];
-
-            if !(0xA1..=0xFE).contains(&second) {
-                return false;
-            }
-            if !(0xA1..=0xFE).contains(&third) {
+            if !is_x0212(data[i + 1], data[i + 2], profile) {
                 return false;
             }
-
-            if second == 0xA1
-                || (0xA3..=0xA5).contains(&second)
-                || second == 0xA8
-                || (0xAC..=0xAF).contains(&second)
-                || (0xEE..=0xFE).contains(&second)
-            {
-                return false;
-            }
-
-            match (second, third) {
-                (0xA2, 0xA1..=0xAE)
-                | (0xA2, 0xBA..=0xC1)
-                | (0xA2, 0xC5..=0xEA)
-                | (0xA2, 0xF2..=0xFE)
-                | (0xA6, 0xA1..=0xE0)
-                | (0xA6, 0xE6)
-                | (0xA6, 0xE8)
-                | (0xA6, 0xEB)
-                | (0xA6, 0xED..=0xF0)
-                | (0xA6, 0xFD..=0xFE)
-                | (0xA7, 0xA1..=0xC1)
-                | (0xA7, 0xCF..=0xF1)
-                | (0xA9, 0xA3)
-                | (0xA9, 0xA5)
-                | (0xA9, 0xA7)
-                | (0xA9, 0xAA)
-                | (0xA9, 0xAE)
-                | (0xA9, 0xB1..=0xC0)
-                | (0xA9, 0xD1..=0xFE)
-                | (0xAA, 0xB9)
-                | (0xAA, 0xF8..=0xFE)
-                | (0xAB, 0xBC)
-                | (0xAB, 0xC4)
-                | (0xAB, 0xF8..=0xFE)
-                | (0xED, 0xE5..=0xFE) => {
-                    return false;
-                }
-                _ => {
-                    i += 3;
-                }
-            }
+            i += 3;
         } else if (0xA1..=0xFE).contains(&lead) || lead == 0x8E {
             // JIS X 0208
             if i + 1 >= data.len() {
@@ -475,12 +500,14 @@ pub fn is_jp_lookup(data: &[u8], table: &[bool; 32768]) -> bool {
             }
 
             i += 2;
+        } else {
+            return false;
         }
     }
     true
 }
 
-fn jp_fallback(slice: &[u8], table: &[i32; 32768]) -> i32 {
+fn jp_fallback(slice: &[u8], table: &[i32; 32768], profile: JpProfile) -> i32 {
     let mut i = 0;
     while i < slice.len() {
         let lead = slice[i];
@@ -490,57 +517,10 @@ fn jp_fallback(slice: &[u8], table: &[i32; 32768]) -> i32 {
             if i + 2 >= slice.len() {
                 return (slice.len() - i) as i32;
             }
-            let second = slice[i + 1];
-            let third = slice[i + 2];
-
-            if !(0xA1..=0xFE).contains(&second) {
+            if !is_x0212(slice[i + 1], slice[i + 2], profile) {
                 return -1;
             }
-            if !(0xA1..=0xFE).contains(&third) {
-                return -1;
-            }
-
-            if second == 0xA1
-                || (0xA3..=0xA5).contains(&second)
-                || second == 0xA8
-                || (0xAC..=0xAF).contains(&second)
-                || (0xEE..=0xFE).contains(&second)
-            {
-                return -1;
-            }
-
-            match (second, third) {
-                (0xA2, 0xA1..=0xAE)
-                | (0xA2, 0xBA..=0xC1)
-                | (0xA2, 0xC5..=0xEA)
-                | (0xA2, 0xF2..=0xFE)
-                | (0xA6, 0xA1..=0xE0)
-                | (0xA6, 0xE6)
-                | (0xA6, 0xE8)
-                | (0xA6, 0xEB)
-                | (0xA6, 0xED..=0xF0)
-                | (0xA6, 0xFD..=0xFE)
-                | (0xA7, 0xA1..=0xC1)
-                | (0xA7, 0xCF..=0xF1)
-                | (0xA9, 0xA3)
-                | (0xA9, 0xA5)
-                | (0xA9, 0xA7)
-                | (0xA9, 0xAA)
-                | (0xA9, 0xAE)
-                | (0xA9, 0xB1..=0xC0)
-                | (0xA9, 0xD1..=0xFE)
-                | (0xAA, 0xB9)
-                | (0xAA, 0xF8..=0xFE)
-                | (0xAB, 0xBC)
-                | (0xAB, 0xC4)
-                | (0xAB, 0xF8..=0xFE)
-                | (0xED, 0xE5..=0xFE) => {
-                    return -1;
-                }
-                _ => {
-                    i += 3;
-                }
-            }
+            i += 3;
         } else if (0xA1..=0xFE).contains(&lead) || lead == 0x8E {
             if i + 1 >= slice.len() {
                 return (slice.len() - i) as i32;
@@ -563,6 +543,21 @@ fn jp_fallback(slice: &[u8], table: &[i32; 32768]) -> i32 {
 static EIGHT_F: __m128i = unsafe { std::mem::transmute([0x8Fu8 as i8; 16]) };
 
 pub fn is_jp_simd(data: &[u8], bitmap: &[i32; 32768]) -> bool {
+    is_jp_simd_profile(data, bitmap, JpProfile::Jis)
+}
+
+pub fn is_jp_simd_profile(data: &[u8], bitmap: &[i32; 32768], profile: JpProfile) -> bool {
+    let i = jp_scan(data, bitmap, profile);
+    i == data.len() || jp_fallback(&data[i..], bitmap, profile) == 0
+}
+
+// Length of the prefix accepted by the SIMD kernel; always a character
+// boundary. Stops at the first block containing an error or at the tail.
+pub fn jp_valid_up_to(data: &[u8], bitmap: &[i32; 32768]) -> usize {
+    jp_scan(data, bitmap, JpProfile::Jis)
+}
+
+fn jp_scan(data: &[u8], bitmap: &[i32; 32768], profile: JpProfile) -> usize {
     let len = data.len();
     let mut i = 0;
     while i + 15 < len {
@@ -585,13 +580,13 @@ pub fn is_jp_simd(data: &[u8], bitmap: &[i32; 32768]) -> bool {
                     let cmp = _mm256_cmpeq_epi32(gathers, tools::ONE);
                     let mask = _mm256_movemask_epi8(cmp);
                     if mask != -1 {
-                        return false;
+                        return i;
                     }
                     i += 16;
                 } else {
-                    let fallback_result = jp_fallback(&data[i..i + 16], bitmap);
+                    let fallback_result = jp_fallback(&data[i..i + 16], bitmap, profile);
                     if fallback_result == -1 {
-                        return false;
+                        return i;
                     } else {
                         i += 16 - fallback_result as usize;
                     }
@@ -601,19 +596,123 @@ pub fn is_jp_simd(data: &[u8], bitmap: &[i32; 32768]) -> bool {
             // all ASCII
             i += 16;
         } else {
-            let fallback_result = jp_fallback(&data[i..i + 16], bitmap);
+            let fallback_result = jp_fallback(&data[i..i + 16], bitmap, profile);
             if fallback_result == -1 {
-                return false;
+                return i;
             } else {
                 i += 16 - fallback_result as usize;
             }
         }
     }
-    if i < len {
-        let fallback_result = jp_fallback(&data[i..], bitmap);
-        if fallback_result != 0 {
-            return false;
+    i
+}
+
+pub fn jp_char_len(data: &[u8], i: usize) -> Result<usize, ErrorKind> {
+    let lead = data[i];
+    // number of bytes and valid structural range of the trail bytes
+    let (len, trail_range) = match lead {
+        0x00..=0x7F => return Ok(1),
+        0x8E => (2, 0xA1..=0xDF),
+        0x8F => (3, 0xA1..=0xFE),
+        0xA1..=0xFE => (2, 0xA1..=0xFE),
+        _ => return Err(ErrorKind::InvalidLead),
+    };
+    for j in 1..len {
+        if i + j >= data.len() {
+            return Err(ErrorKind::Truncated);
+        }
+        if !trail_range.contains(&data[i + j]) {
+            return Err(ErrorKind::InvalidTrail);
         }
     }
-    true
+    let assigned = match lead {
+        0x8E => true,
+        0x8F => is_x0212(data[i + 1], data[i + 2], JpProfile::Jis),
+        _ => is_x0208(lead, data[i + 1], JpProfile::Jis),
+    };
+    if !assigned {
+        return Err(ErrorKind::Unassigned);
+    }
+    Ok(len)
+}
+
+// Unicode scalar of each valid JIS X 0208 pair, indexed like the validity
+// table, as encoding_rs decodes it; zero for invalid pairs.
+pub fn build_jp_unicode_table() -> [u16; 32768] {
+    tools::build_unicode_table(&build_jp_table(), EUC_JP)
+}
+
+// The same under a vendor profile. Pairs that encoding_rs does not map,
+// such as the user-defined rows of eucJP-ms, come out as U+FFFD.
+pub fn build_jp_profile_unicode_table(profile: JpProfile) -> [u16; 32768] {
+    tools::build_unicode_table(&build_jp_profile_table(profile), EUC_JP)
+}
+
+// The same for the JIS X 0212 pairs that follow 0x8F.
+pub fn build_jp_x0212_unicode_table() -> [u16; 32768] {
+    build_jp_x0212_profile_unicode_table(JpProfile::Jis)
+}
+
+pub fn build_jp_x0212_profile_unicode_table(profile: JpProfile) -> [u16; 32768] {
+    let mut unicode = [0; 32768];
+    for second in 0xA1..=0xFE {
+        for third in 0xA1..=0xFE {
+            if is_x0212(second, third, profile) {
+                unicode[(second - 0x80) as usize * 256 + third as usize] =
+                    tools::decode_bmp(EUC_JP, &[0x8F, second, third]);
+            }
+        }
+    }
+    unicode
+}
+
+// Validates and decodes in one pass, with `tools::build_utf8_table` of
+// `build_jp_unicode_table` and `build_jp_x0212_unicode_table`. ASCII runs
+// are copied in blocks; the output is the one of encoding_rs for valid
+// input.
+pub fn jp_decode_to_utf8(
+    data: &[u8],
+    x0208: &tools::Utf8Table,
+    x0212: &tools::Utf8Table,
+) -> Result<String, ValidationError> {
+    let mut out = tools::Utf8Output::for_input(data.len());
+    let mut i = 0;
+    while i < data.len() {
+        let lead = data[i];
+        if lead < 0x80 {
+            // the ASCII bytes from `i` on are not pushed yet
+            i += unsafe { out.push_ascii(&data[i..]) };
+            continue;
+        }
+        let (len, entry) = match lead {
+            // half-width katakana
+            0x8E if i + 1 < data.len() && (0xA1..=0xDF).contains(&data[i + 1]) => {
+                let c = char::from_u32(0xFF61 + (data[i + 1] - 0xA1) as u32).unwrap();
+                // two bytes for three in UTF-8
+                unsafe { out.push_char(c) };
+                i += 2;
+                continue;
+            }
+            0x8F if i + 2 < data.len() => (
+                3,
+                x0212[(data[i + 1] as usize).saturating_sub(0x80) * 256 + data[i + 2] as usize],
+            ),
+            0xA1..=0xFE if i + 1 < data.len() => (
+                2,
+                x0208[(lead - 0x80) as usize * 256 + data[i + 1] as usize],
+            ),
+            _ => (0, 0),
+        };
+        if entry == 0 {
+            return Err(ValidationError {
+                encoding: Encoding::EucJp,
+                offset: i,
+                kind: jp_char_len(data, i).err().unwrap_or(ErrorKind::Unassigned),
+            });
+        }
+        // two or three bytes, consumed below
+        unsafe { out.push_entry(entry) };
+        i += len;
+    }
+    Ok(out.into_string())
 }
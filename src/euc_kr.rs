@@ -1,3 +1,5 @@
+use crate::encoding::Encoding;
+use crate::error::{ErrorKind, ValidationError};
 use crate::tools;
 use encoding_rs::EUC_KR;
 use std::arch::x86_64::*;
@@ -9,7 +11,45 @@ pub fn is_kr_rs(data: &[u8]) -> bool {
     tools::is_encoding_rs(data, EUC_KR)
 }
 
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum KrRepertoire {
+    #[default]
+    KsX1001,
+    // North Korean KPS 9566, same EUC structure with a different layout
+    Kps9566,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct KrOptions {
+    // accept the eight-byte Hangul composition sequences of KS X 1001:
+    // filler 0xA4D4 followed by an initial, a medial and a final jamo
+    pub composition: bool,
+    // accept the user-defined rows 0xC9 and 0xFE; KPS 9566 assigns
+    // characters there, so this has no effect with that repertoire
+    pub user_defined: bool,
+    pub repertoire: KrRepertoire,
+}
+
+// where a character outside plain KS X 1001 came from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KrRegion {
+    UserDefined,
+    Composed,
+    Kps9566,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KrMark {
+    pub offset: usize,
+    pub len: usize,
+    pub region: KrRegion,
+}
+
 pub fn is_kr_range(data: &[u8]) -> bool {
+    is_kr_range_with(data, KrOptions::default())
+}
+
+pub fn is_kr_range_with(data: &[u8], options: KrOptions) -> bool {
     let mut i = 0;
     while i < data.len() {
         let lead = data[i];
@@ -22,34 +62,17 @@ pub fn is_kr_range(data: &[u8]) -> bool {
                     return false;
                 }
                 let trail = data[i + 1];
-                if !(0xA1..=0xFE).contains(&trail) {
-                    return false;
-                }
-                if (0xAD..=0xAF).contains(&lead) || lead == 0xC9 || lead == 0xFE {
-                    return false;
-                }
-                match (lead, trail) {
-                    (0xA2, 0xE8..=0xFE)
-                    | (0xA4, 0xD4)
-                    | (0xA5, 0xAB..=0xAF)
-                    | (0xA5, 0xBA..=0xC0)
-                    | (0xA5, 0xD9..=0xE0)
-                    | (0xA5, 0xF9..=0xFE)
-                    | (0xA6, 0xE5..=0xFE)
-                    | (0xA7, 0xF0..=0xFE)
-                    | (0xA8, 0xA5)
-                    | (0xA8, 0xA7)
-                    | (0xA8, 0xB0)
-                    | (0xAA, 0xF4..=0xFE)
-                    | (0xAB, 0xF7..=0xFE)
-                    | (0xAC, 0xC2..=0xD0)
-                    | (0xAC, 0xF2..=0xFE) => {
+                if options.composition && lead == 0xA4 && trail == FILLER {
+                    if !is_composed_syllable(&data[i..]) {
                         return false;
                     }
-                    _ => {
-                        i += 2;
-                    }
+                    i += 8;
+                    continue;
+                }
+                if !is_kr_pair(lead, trail, options) {
+                    return false;
                 }
+                i += 2;
             }
             _ => return false,
         }
@@ -57,7 +80,90 @@ pub fn is_kr_range(data: &[u8]) -> bool {
     true
 }
 
-static VALID_KR_RANGES: [&[(u8, u8)]; 128] = [
+fn is_user_defined(lead: u8, trail: u8) -> bool {
+    (lead == 0xC9 || lead == 0xFE) && (0xA1..=0xFE).contains(&trail)
+}
+
+// rows 0xC9 and 0xFE hold characters in KPS 9566, so they are only
+// user-defined under KS X 1001
+fn is_user_defined_with(lead: u8, trail: u8, options: KrOptions) -> bool {
+    options.user_defined
+        && options.repertoire == KrRepertoire::KsX1001
+        && is_user_defined(lead, trail)
+}
+
+fn is_kr_pair(lead: u8, trail: u8, options: KrOptions) -> bool {
+    if is_user_defined_with(lead, trail, options) {
+        return true;
+    }
+    match options.repertoire {
+        KrRepertoire::KsX1001 => is_ks_x1001(lead, trail),
+        KrRepertoire::Kps9566 => tools::in_ranges(VALID_KPS_RANGES[(lead - 0x80) as usize], trail),
+    }
+}
+
+fn is_ks_x1001(lead: u8, trail: u8) -> bool {
+    if !(0xA1..=0xFE).contains(&trail) {
+        return false;
+    }
+    if (0xAD..=0xAF).contains(&lead) || lead == 0xC9 || lead == 0xFE {
+        return false;
+    }
+    !matches!(
+        (lead, trail),
+        (0xA2, 0xE8..=0xFE)
+            | (0xA4, 0xD4)
+            | (0xA5, 0xAB..=0xAF)
+            | (0xA5, 0xBA..=0xC0)
+            | (0xA5, 0xD9..=0xE0)
+            | (0xA5, 0xF9..=0xFE)
+            | (0xA6, 0xE5..=0xFE)
+            | (0xA7, 0xF0..=0xFE)
+            | (0xA8, 0xA5)
+            | (0xA8, 0xA7)
+            | (0xA8, 0xB0)
+            | (0xAA, 0xF4..=0xFE)
+            | (0xAB, 0xF7..=0xFE)
+            | (0xAC, 0xC2..=0xD0)
+            | (0xAC, 0xF2..=0xFE)
+    )
+}
+
+// Hangul filler, the trail byte of 0xA4D4
+const FILLER: u8 = 0xD4;
+
+// trail bytes of the row 0xA4 jamo allowed in each slot of a composition sequence
+fn is_initial(trail: u8) -> bool {
+    matches!(
+        trail,
+        0xA1 | 0xA2 | 0xA4 | 0xA7 | 0xA8 | 0xA9 | 0xB1..=0xB3 | 0xB5..=0xBE
+    )
+}
+
+fn is_medial(trail: u8) -> bool {
+    (0xBF..=0xD3).contains(&trail)
+}
+
+fn is_final(trail: u8) -> bool {
+    matches!(
+        trail,
+        0xA1..=0xA7 | 0xA9..=0xB2 | 0xB4..=0xB8 | 0xBA..=0xBE | FILLER
+    )
+}
+
+// `seq` starts at a filler; a modern syllable needs an initial and a medial,
+// the final may be the filler
+fn is_composed_syllable(seq: &[u8]) -> bool {
+    if seq.len() < 8 {
+        return false;
+    }
+    if seq[2] != 0xA4 || seq[4] != 0xA4 || seq[6] != 0xA4 {
+        return false;
+    }
+    is_initial(seq[3]) && is_medial(seq[5]) && is_final(seq[7])
+}
+
+pub(crate) static VALID_KR_RANGES: [&[(u8, u8)]; 128] = [
     // 0x80-0xA0, not any valid bytes
     &[],
     &[],
@@ -101,7 +207,7 @@ static VALID_KR_RANGES: [&[(u8, u8)]; 128] = [
     // 0xA4
     &[(0xA1, 0xD3), (0xD5, 0xFE)],
     // 0xA5
-    &[(0xA1, 0xAA), (0xB1, 0xB9), (0xC1, 0xD8), (0xE1, 0xF8)],
+    &[(0xA1, 0xAA), (0xB0, 0xB9), (0xC1, 0xD8), (0xE1, 0xF8)],
     // 0xA6
     &[(0xA1, 0xE4)],
     // 0xA7
@@ -284,11 +390,109 @@ static VALID_KR_RANGES: [&[(u8, u8)]; 128] = [
     &[],
 ];
 
+// KPS 9566: symbols in rows 1-12, laid out as in KS X 1001, 2,679 Hangul
+// syllables from row 16 to the middle of row 44 and 4,653 hanja from row 45
+// to the middle of row 94. Rows 13-15 are unassigned.
+static VALID_KPS_RANGES: [&[(u8, u8)]; 128] = {
+    let mut ranges: [&[(u8, u8)]; 128] = [&[]; 128];
+    let mut lead = 0xA1;
+    while lead <= 0xAC {
+        ranges[lead - 0x80] = VALID_KR_RANGES[lead - 0x80];
+        lead += 1;
+    }
+    lead = 0xB0;
+    while lead <= 0xFD {
+        ranges[lead - 0x80] = &[(0xA1, 0xFE)];
+        lead += 1;
+    }
+    ranges[0xCC - 0x80] = &[(0xA1, 0xCF)];
+    ranges[0xFE - 0x80] = &[(0xA1, 0xCF)];
+    ranges
+};
+
 pub fn build_kr_table() -> [bool; 32768] {
     tools::build_table(&VALID_KR_RANGES)
 }
 
+// Table for the `_with` validators; the SIMD one takes it through
+// `tools::build_i32_table_from_bool`.
+pub fn build_kr_table_with(options: KrOptions) -> [bool; 32768] {
+    let mut ranges = match options.repertoire {
+        KrRepertoire::KsX1001 => VALID_KR_RANGES,
+        KrRepertoire::Kps9566 => VALID_KPS_RANGES,
+    };
+    if options.user_defined && options.repertoire == KrRepertoire::KsX1001 {
+        ranges[0xC9 - 0x80] = &[(0xA1, 0xFE)];
+        ranges[0xFE - 0x80] = &[(0xA1, 0xFE)];
+    }
+    let mut table = tools::build_table(&ranges);
+    // the filler only starts a composition sequence; keeping it out of the
+    // table sends every block holding one to the SIMD fallback
+    table[(0xA4 - 0x80) * 256 + FILLER as usize] = false;
+    table
+}
+
+// Lists the characters that are valid under `options` but not in plain
+// KS X 1001, or `None` if the input is invalid under `options`. Under
+// KPS 9566 that is every pair: the two layouts differ even where both
+// assign a code position, and there is no mapping here to tell the few
+// that read the same.
+pub fn find_kr_nonstandard(data: &[u8], options: KrOptions) -> Option<Vec<KrMark>> {
+    let mut marks = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        let lead = data[i];
+        match lead {
+            0x00..=0x7F => {
+                i += 1;
+            }
+            0xA1..=0xFE => {
+                if i + 1 >= data.len() {
+                    return None;
+                }
+                let trail = data[i + 1];
+                if options.composition && lead == 0xA4 && trail == FILLER {
+                    if !is_composed_syllable(&data[i..]) {
+                        return None;
+                    }
+                    marks.push(KrMark {
+                        offset: i,
+                        len: 8,
+                        region: KrRegion::Composed,
+                    });
+                    i += 8;
+                    continue;
+                }
+                if !is_kr_pair(lead, trail, options) {
+                    return None;
+                }
+                let region = if is_user_defined_with(lead, trail, options) {
+                    Some(KrRegion::UserDefined)
+                } else if options.repertoire == KrRepertoire::Kps9566 {
+                    Some(KrRegion::Kps9566)
+                } else {
+                    None
+                };
+                if let Some(region) = region {
+                    marks.push(KrMark {
+                        offset: i,
+                        len: 2,
+                        region,
+                    });
+                }
+                i += 2;
+            }
+            _ => return None,
+        }
+    }
+    Some(marks)
+}
+
 pub fn is_kr_lookup(data: &[u8], table: &[bool; 32768]) -> bool {
+    is_kr_lookup_with(data, table, KrOptions::default())
+}
+
+pub fn is_kr_lookup_with(data: &[u8], table: &[bool; 32768], options: KrOptions) -> bool {
     let mut i = 0;
     while i < data.len() {
         let lead = data[i];
@@ -301,6 +505,13 @@ pub fn is_kr_lookup(data: &[u8], table: &[bool; 32768]) -> bool {
                     return false;
                 }
                 let trail = data[i + 1];
+                if options.composition && lead == 0xA4 && trail == FILLER {
+                    if !is_composed_syllable(&data[i..]) {
+                        return false;
+                    }
+                    i += 8;
+                    continue;
+                }
                 let index = (lead - 0x80) as usize * 256 + trail as usize;
                 if !table[index] {
                     return false;
@@ -342,7 +553,9 @@ fn kr_fallback(slice: &[u8], table: &[i32; 32768]) -> i32 {
     0
 }
 
-pub fn is_kr_simd(data: &[u8], table: &[i32; 32768]) -> bool {
+// Length of the prefix accepted by the SIMD kernel; always a character
+// boundary. Stops at the first block containing an error or at the tail.
+pub fn kr_valid_up_to(data: &[u8], table: &[i32; 32768]) -> usize {
     let len = data.len();
     let mut i = 0;
 
@@ -364,7 +577,7 @@ pub fn is_kr_simd(data: &[u8], table: &[i32; 32768]) -> bool {
                 let cmp = _mm256_cmpeq_epi32(gathers, tools::ONE);
                 let mask = _mm256_movemask_epi8(cmp);
                 if mask != -1 {
-                    return false;
+                    return i;
                 }
                 i += 16;
             } else if mask == 0 {
@@ -372,20 +585,178 @@ pub fn is_kr_simd(data: &[u8], table: &[i32; 32768]) -> bool {
                 i += 16;
             } else {
                 // mixed single-byte and two-byte characters
-                let fallback_result = kr_fallback(&data[i..i + 16], &table);
+                let fallback_result = kr_fallback(&data[i..i + 16], table);
                 if fallback_result == -1 {
-                    return false;
+                    return i;
                 } else {
                     i += 16 - fallback_result as usize;
                 }
             }
         }
     }
-    if i < len {
-        let fallback_result = kr_fallback(&data[i..], &table);
-        if fallback_result == -1 {
-            return false;
+    i
+}
+
+pub fn is_kr_simd(data: &[u8], table: &[i32; 32768]) -> bool {
+    let i = kr_valid_up_to(data, table);
+    // a character cut off at the end is invalid as well
+    i == data.len() || kr_fallback(&data[i..], table) == 0
+}
+
+// Plain KS X 1001, as `Encoding::EucKr` validates; `KrOptions` only reach
+// the `_with` validators and `find_kr_nonstandard`.
+pub fn kr_char_len(data: &[u8], i: usize) -> Result<usize, ErrorKind> {
+    let lead = data[i];
+    if lead <= 0x7F {
+        return Ok(1);
+    }
+    if !(0xA1..=0xFE).contains(&lead) {
+        return Err(ErrorKind::InvalidLead);
+    }
+    if i + 1 >= data.len() {
+        return Err(ErrorKind::Truncated);
+    }
+    let trail = data[i + 1];
+    if !(0xA1..=0xFE).contains(&trail) {
+        return Err(ErrorKind::InvalidTrail);
+    }
+    if !is_ks_x1001(lead, trail) {
+        return Err(ErrorKind::Unassigned);
+    }
+    Ok(2)
+}
+
+// Validates the characters starting in `data[start..end]`; a composition
+// sequence may run past `end`. Returns the offset after the last character.
+fn kr_composed_fallback(
+    data: &[u8],
+    start: usize,
+    end: usize,
+    table: &[i32; 32768],
+) -> Option<usize> {
+    let mut i = start;
+    while i < end {
+        let lead = data[i];
+        match lead {
+            0x00..=0x7F => {
+                i += 1;
+            }
+            0xA1..=0xFE => {
+                if i + 1 >= data.len() {
+                    return None;
+                }
+                let trail = data[i + 1];
+                if lead == 0xA4 && trail == FILLER {
+                    if !is_composed_syllable(&data[i..]) {
+                        return None;
+                    }
+                    i += 8;
+                    continue;
+                }
+                let index = (lead - 0x80) as usize * 256 + trail as usize;
+                if table[index] != 1 {
+                    return None;
+                }
+                i += 2;
+            }
+            _ => return None,
         }
     }
-    true
+    Some(i)
+}
+
+pub fn is_kr_simd_with(data: &[u8], table: &[i32; 32768], options: KrOptions) -> bool {
+    if !options.composition {
+        return is_kr_simd(data, table);
+    }
+    let len = data.len();
+    let mut i = 0;
+
+    while i + 15 < len {
+        let chunk = unsafe { _mm_loadu_si128(data[i..].as_ptr() as *const __m128i) };
+        let mask = unsafe { _mm_movemask_epi8(chunk) };
+        if mask == 0 {
+            // all single-byte characters
+            i += 16;
+            continue;
+        }
+        if mask == 0xFFFF {
+            // all two-byte characters; a failed gather may still be a
+            // composition sequence, which the fallback checks
+            let valid = unsafe {
+                let leads = _mm_shuffle_epi8(chunk, tools::LEAD_BYTE_MASK);
+                let leads = _mm_cvtepu8_epi16(leads);
+                let trails = _mm_shuffle_epi8(chunk, tools::TRAIL_BYTE_MASK);
+                let trails = _mm_cvtepu8_epi16(trails);
+                let sub_leads = _mm_sub_epi16(leads, tools::EIGHTY);
+                let shift_leads = _mm_slli_epi16::<8>(sub_leads);
+                let indices = _mm_or_si128(shift_leads, trails);
+                let indices = _mm256_cvtepu16_epi32(indices);
+                let gathers = _mm256_i32gather_epi32::<4>(table.as_ptr(), indices);
+                let cmp = _mm256_cmpeq_epi32(gathers, tools::ONE);
+                _mm256_movemask_epi8(cmp) == -1
+            };
+            if valid {
+                i += 16;
+                continue;
+            }
+        }
+        match kr_composed_fallback(data, i, i + 16, table) {
+            Some(next) => i = next,
+            None => return false,
+        }
+    }
+    i >= len || kr_composed_fallback(data, i, len, table).is_some()
+}
+
+// Unicode scalar of each valid pair, indexed like the validity table, as
+// encoding_rs decodes it; zero for invalid pairs.
+pub fn build_kr_unicode_table() -> [u16; 32768] {
+    tools::build_unicode_table(&build_kr_table(), EUC_KR)
+}
+
+// Validates and decodes in one pass, with `tools::build_utf8_table` of the
+// table above. ASCII runs are copied in blocks; the output is the one of
+// encoding_rs for valid input.
+pub fn kr_decode_to_utf8(data: &[u8], utf8: &tools::Utf8Table) -> Result<String, ValidationError> {
+    let mut out = tools::Utf8Output::for_input(data.len());
+    let mut i = 0;
+    while i < data.len() {
+        let lead = data[i];
+        if lead < 0x80 {
+            // the ASCII bytes from `i` on are not pushed yet
+            i += unsafe { out.push_ascii(&data[i..]) };
+            continue;
+        }
+        if lead >= 0xA1 && i + 1 < data.len() {
+            let entry = utf8[(lead - 0x80) as usize * 256 + data[i + 1] as usize];
+            if entry != 0 {
+                // a pair, consumed below
+                unsafe { out.push_entry(entry) };
+                i += 2;
+                continue;
+            }
+        }
+        return Err(ValidationError {
+            encoding: Encoding::EucKr,
+            offset: i,
+            kind: kr_char_len(data, i).err().unwrap_or(ErrorKind::Unassigned),
+        });
+    }
+    Ok(out.into_string())
+}
+
+// Unicode scalar of each pair of CP949, the Unified Hangul Code superset
+// that encoding_rs decodes as EUC-KR; zero for pairs it does not map.
+pub fn build_cp949_unicode_table() -> [u16; 32768] {
+    let mut unicode = [0; 32768];
+    for lead in 0x81..=0xFE {
+        for trail in 0x41..=0xFE {
+            let code_point = tools::decode_bmp(EUC_KR, &[lead, trail]);
+            if code_point != 0xFFFD {
+                unicode[(lead - 0x80) as usize * 256 + trail as usize] = code_point;
+            }
+        }
+    }
+    unicode
 }
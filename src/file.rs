@@ -0,0 +1,193 @@
+use crate::encoding::{Encoding, Strategy};
+use crate::error::ValidationError;
+use crate::reader::{self, ValidatingReader};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read};
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use std::ptr;
+
+#[derive(Debug)]
+pub enum FileError {
+    Io(io::Error),
+    Invalid(ValidationError),
+    // The strategy rejected the input, but the SIMD validator, which
+    // locates errors, accepts it. iconv and encoding_rs accept slightly
+    // different repertoires.
+    Rejected {
+        encoding: Encoding,
+        strategy: Strategy,
+    },
+}
+
+impl fmt::Display for FileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FileError::Io(error) => write!(f, "I/O error: {}", error),
+            FileError::Invalid(error) => write!(f, "{}", error),
+            FileError::Rejected { encoding, strategy } => {
+                write!(f, "invalid {} according to {}", encoding, strategy)
+            }
+        }
+    }
+}
+
+impl std::error::Error for FileError {}
+
+impl From<io::Error> for FileError {
+    fn from(error: io::Error) -> Self {
+        match reader::validation_error(&error) {
+            Some(&validation) => FileError::Invalid(validation),
+            None => FileError::Io(error),
+        }
+    }
+}
+
+impl From<ValidationError> for FileError {
+    fn from(error: ValidationError) -> Self {
+        FileError::Invalid(error)
+    }
+}
+
+// A read-only private mapping of a whole regular file.
+pub struct MappedFile {
+    ptr: *mut libc::c_void,
+    len: usize,
+}
+
+impl MappedFile {
+    // Maps `file` and advises the kernel that it will be read sequentially.
+    // Fails for files that cannot be mapped, such as pipes and most
+    // special files.
+    pub fn new(file: &File) -> io::Result<MappedFile> {
+        let len = file.metadata()?.len() as usize;
+        if len == 0 {
+            return Ok(MappedFile {
+                ptr: ptr::null_mut(),
+                len: 0,
+            });
+        }
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        // only a hint, a failure is harmless
+        unsafe {
+            libc::madvise(ptr, len, libc::MADV_SEQUENTIAL);
+        }
+        Ok(MappedFile { ptr, len })
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        if self.len == 0 {
+            return &[];
+        }
+        unsafe { std::slice::from_raw_parts(self.ptr as *const u8, self.len) }
+    }
+}
+
+impl Drop for MappedFile {
+    fn drop(&mut self) {
+        if self.len > 0 {
+            unsafe {
+                libc::munmap(self.ptr, self.len);
+            }
+        }
+    }
+}
+
+// A whole file in memory, memory-mapped when it is a regular file.
+pub enum Contents {
+    Mapped(MappedFile),
+    Read(Vec<u8>),
+}
+
+impl Contents {
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Contents> {
+        let mut file = File::open(path)?;
+        let metadata = file.metadata()?;
+        if metadata.is_file()
+            && metadata.len() > 0
+            && let Ok(mapped) = MappedFile::new(&file)
+        {
+            return Ok(Contents::Mapped(mapped));
+        }
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+        Ok(Contents::Read(data))
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        match self {
+            Contents::Mapped(mapped) => mapped.as_slice(),
+            Contents::Read(data) => data,
+        }
+    }
+}
+
+// Same checks as `Encoding::is_valid_with`, with the error located: by the
+// SIMD validator for `Strategy::Simd`, otherwise only once the strategy
+// has rejected the input. An empty input is valid whatever the strategy,
+// though the encoding_rs validators reject it.
+pub fn validate_with(data: &[u8], encoding: Encoding, strategy: Strategy) -> Result<(), FileError> {
+    if data.is_empty() {
+        return Ok(());
+    }
+    if strategy == Strategy::Simd {
+        return Ok(encoding.validate(data)?);
+    }
+    if encoding.is_valid_with(data, strategy) {
+        return Ok(());
+    }
+    match encoding.validate(data) {
+        Err(error) => Err(FileError::Invalid(error)),
+        Ok(()) => Err(FileError::Rejected { encoding, strategy }),
+    }
+}
+
+// Validates a stream. The SIMD strategy validates as it reads; the others
+// need the whole input in memory.
+pub fn validate_reader<R: Read>(
+    mut reader: R,
+    encoding: Encoding,
+    strategy: Strategy,
+) -> Result<(), FileError> {
+    if strategy == Strategy::Simd {
+        let mut reader = ValidatingReader::new(reader, encoding);
+        io::copy(&mut reader, &mut io::sink())?;
+        return Ok(());
+    }
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+    validate_with(&data, encoding, strategy)
+}
+
+// Regular files are memory-mapped; pipes, devices, files that report no
+// size (as in /proc) and files that cannot be mapped are read through a
+// buffer instead. As with any mapping, truncating the file while it is
+// validated raises SIGBUS.
+pub fn validate_file<P: AsRef<Path>>(
+    path: P,
+    encoding: Encoding,
+    strategy: Strategy,
+) -> Result<(), FileError> {
+    let file = File::open(path)?;
+    let metadata = file.metadata()?;
+    if metadata.is_file()
+        && metadata.len() > 0
+        && let Ok(mapped) = MappedFile::new(&file)
+    {
+        return validate_with(mapped.as_slice(), encoding, strategy);
+    }
+    validate_reader(file, encoding, strategy)
+}
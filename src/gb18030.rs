@@ -1,3 +1,5 @@
+use crate::encoding::Encoding;
+use crate::error::{ErrorKind, ValidationError};
 use crate::tools;
 use encoding_rs::GB18030;
 use std::arch::x86_64::*;
@@ -111,17 +113,26 @@ pub static SEVEN_F: __m128i = unsafe { std::mem::transmute([0x7Fu8 as i8; 16]) }
 pub static EIGHT_ONE: __m128i = unsafe { std::mem::transmute([0x81u8 as i8; 16]) };
 pub static FE: __m128i = unsafe { std::mem::transmute([0xFEu8 as i8; 16]) };
 
+// lead bytes sit at the even positions of a block of pairs, trail bytes at the odd ones
+static EVEN_BYTES: __m128i =
+    unsafe { std::mem::transmute([-1i8, 0, -1, 0, -1, 0, -1, 0, -1, 0, -1, 0, -1, 0, -1, 0]) };
+static ODD_BYTES: __m128i =
+    unsafe { std::mem::transmute([0i8, -1, 0, -1, 0, -1, 0, -1, 0, -1, 0, -1, 0, -1, 0, -1]) };
+
 #[inline]
 unsafe fn validate_byte_pairs_128(data: __m128i) -> bool {
-    let lead_in_range = _mm_and_si128(mm_compge_epu8(data, EIGHT_ONE), mm_comple_epu8(data, FE));
+    unsafe {
+        let lead_in_range =
+            _mm_and_si128(mm_compge_epu8(data, EIGHT_ONE), mm_comple_epu8(data, FE));
 
-    let tail_in_range = _mm_and_si128(mm_compge_epu8(data, FORTY), mm_comple_epu8(data, FE));
-    let seven_in_range = _mm_cmpeq_epi8(data, SEVEN_F);
-    let seven_mask = _mm_and_si128(seven_in_range, tools::TRAIL_BYTE_MASK);
+        let tail_in_range = _mm_and_si128(mm_compge_epu8(data, FORTY), mm_comple_epu8(data, FE));
+        let seven_in_range = _mm_cmpeq_epi8(data, SEVEN_F);
+        let seven_mask = _mm_and_si128(seven_in_range, ODD_BYTES);
 
-    _mm_testc_si128(lead_in_range, tools::LEAD_BYTE_MASK) != 0
-        && _mm_testc_si128(tail_in_range, tools::TRAIL_BYTE_MASK) != 0
-        && _mm_movemask_epi8(seven_mask) == 0
+        _mm_testc_si128(lead_in_range, EVEN_BYTES) != 0
+            && _mm_testc_si128(tail_in_range, ODD_BYTES) != 0
+            && _mm_movemask_epi8(seven_mask) == 0
+    }
 }
 
 fn gb18030_fallback(data: &[u8], current: usize) -> i32 {
@@ -198,7 +209,9 @@ fn gb18030_fallback(data: &[u8], current: usize) -> i32 {
     i as i32
 }
 
-pub fn is_gb18030_simd(data: &[u8]) -> bool {
+// Length of the prefix accepted by the SIMD kernel; always a character
+// boundary. Stops at the first invalid character or at the tail.
+pub fn gb18030_valid_up_to(data: &[u8]) -> usize {
     let len = data.len();
     let mut i = 0;
     while i + 15 < len {
@@ -210,14 +223,164 @@ pub fn is_gb18030_simd(data: &[u8]) -> bool {
         }
         let fallback_result = gb18030_fallback(data, i);
         if fallback_result == -1 {
-            return false;
+            return i;
         } else {
             i = fallback_result as usize;
         }
     }
-    if i < len {
-        let slice = &data[i..];
-        return is_gb18030_fsm(slice);
+    i
+}
+
+pub fn is_gb18030_simd(data: &[u8]) -> bool {
+    let i = gb18030_valid_up_to(data);
+    i == data.len() || is_gb18030_fsm(&data[i..])
+}
+
+pub fn gb18030_char_len(data: &[u8], i: usize) -> Result<usize, ErrorKind> {
+    let b1 = data[i];
+    match b1 {
+        0x00..=0x7F => return Ok(1),
+        0x81..=0xFE => {}
+        _ => return Err(ErrorKind::InvalidLead),
+    }
+    if i + 1 >= data.len() {
+        return Err(ErrorKind::Truncated);
+    }
+    let b2 = data[i + 1];
+    match b2 {
+        0x40..=0x7E | 0x80..=0xFE => return Ok(2),
+        0x30..=0x39 => {}
+        _ => return Err(ErrorKind::InvalidTrail),
+    }
+    if i + 2 >= data.len() {
+        return Err(ErrorKind::Truncated);
+    }
+    let b3 = data[i + 2];
+    if !(0x81..=0xFE).contains(&b3) {
+        return Err(ErrorKind::InvalidTrail);
+    }
+    if i + 3 >= data.len() {
+        return Err(ErrorKind::Truncated);
+    }
+    let b4 = data[i + 3];
+    if !(0x30..=0x39).contains(&b4) {
+        return Err(ErrorKind::InvalidTrail);
+    }
+    let val = u32::from_be_bytes([b1, b2, b3, b4]);
+    if (val > 0x8431A439 && val < 0x90308130) || val > 0xE3329A35 {
+        return Err(ErrorKind::OutOfRange);
+    }
+    Ok(4)
+}
+
+// Four-byte sequences up to 0x8431A439 map into the BMP; this many.
+pub const FOUR_BYTE_BMP: usize = 39420;
+// Linear index of 0x90308130, the first supplementary-plane sequence,
+// which maps to U+10000.
+const FOUR_BYTE_SUPPLEMENTARY: u32 = 189000;
+
+// Unicode scalar of each two-byte sequence, indexed by
+// `(lead - 0x80) * 256 + trail`, as encoding_rs decodes it; zero for
+// invalid pairs.
+pub fn build_gb18030_unicode_table() -> [u16; 32768] {
+    let mut unicode = [0; 32768];
+    for lead in 0x81..=0xFE {
+        for trail in (0x40..=0x7E).chain(0x80..=0xFE) {
+            unicode[(lead - 0x80) as usize * 256 + trail as usize] =
+                tools::decode_bmp(GB18030, &[lead, trail]);
+        }
+    }
+    unicode
+}
+
+// The four-byte sequence of a linear index.
+pub fn four_byte_sequence(index: u32) -> [u8; 4] {
+    [
+        (index / 12600) as u8 + 0x81,
+        (index / 1260 % 10) as u8 + 0x30,
+        (index / 10 % 126) as u8 + 0x81,
+        (index % 10) as u8 + 0x30,
+    ]
+}
+
+// The four-byte sequence of a character outside the BMP.
+pub fn supplementary_sequence(c: char) -> [u8; 4] {
+    four_byte_sequence(c as u32 - 0x10000 + FOUR_BYTE_SUPPLEMENTARY)
+}
+
+// Unicode scalar of the four-byte sequences that map into the BMP, by
+// linear index.
+pub fn build_gb18030_four_byte_table() -> Vec<u16> {
+    (0..FOUR_BYTE_BMP as u32)
+        .map(|index| tools::decode_bmp(GB18030, &four_byte_sequence(index)))
+        .collect()
+}
+
+// The character of the four-byte sequence at `data[i..]`, if it is one.
+fn four_byte_char(data: &[u8], i: usize, four_byte: &[u16]) -> Option<char> {
+    if i + 3 >= data.len()
+        || !(0x81..=0xFE).contains(&data[i + 2])
+        || !(0x30..=0x39).contains(&data[i + 3])
+    {
+        return None;
+    }
+    let index = (data[i] as u32 - 0x81) * 12600
+        + (data[i + 1] as u32 - 0x30) * 1260
+        + (data[i + 2] as u32 - 0x81) * 10
+        + (data[i + 3] as u32 - 0x30);
+    let code_point = if (index as usize) < FOUR_BYTE_BMP {
+        four_byte[index as usize] as u32
+    } else if (FOUR_BYTE_SUPPLEMENTARY..FOUR_BYTE_SUPPLEMENTARY + 0x100000).contains(&index) {
+        index - FOUR_BYTE_SUPPLEMENTARY + 0x10000
+    } else {
+        0
+    };
+    char::from_u32(code_point).filter(|&c| c != '\0')
+}
+
+// Validates and decodes in one pass, with `tools::build_utf8_table` of the
+// two-byte table and the four-byte table above. ASCII runs are copied in
+// blocks; the output is the one of encoding_rs for valid input.
+pub fn gb18030_decode_to_utf8(
+    data: &[u8],
+    two_byte: &tools::Utf8Table,
+    four_byte: &[u16],
+) -> Result<String, ValidationError> {
+    let mut out = tools::Utf8Output::for_input(data.len());
+    let mut i = 0;
+    while i < data.len() {
+        let lead = data[i];
+        if lead < 0x80 {
+            // the ASCII bytes from `i` on are not pushed yet
+            i += unsafe { out.push_ascii(&data[i..]) };
+            continue;
+        }
+        if (0x81..=0xFE).contains(&lead) && i + 1 < data.len() {
+            let second = data[i + 1];
+            if (0x30..=0x39).contains(&second) {
+                if let Some(c) = four_byte_char(data, i, four_byte) {
+                    // four bytes, at most as many in UTF-8
+                    unsafe { out.push_char(c) };
+                    i += 4;
+                    continue;
+                }
+            } else {
+                let entry = two_byte[(lead - 0x80) as usize * 256 + second as usize];
+                if entry != 0 {
+                    // a pair, consumed below
+                    unsafe { out.push_entry(entry) };
+                    i += 2;
+                    continue;
+                }
+            }
+        }
+        return Err(ValidationError {
+            encoding: Encoding::Gb18030,
+            offset: i,
+            kind: gb18030_char_len(data, i)
+                .err()
+                .unwrap_or(ErrorKind::Unassigned),
+        });
     }
-    true
+    Ok(out.into_string())
 }
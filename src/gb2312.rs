@@ -1,3 +1,5 @@
+use crate::encoding::Encoding;
+use crate::error::{ErrorKind, ValidationError};
 use crate::tools;
 use encoding_rs::GBK;
 use std::arch::x86_64::*;
@@ -58,7 +60,7 @@ pub fn is_gb2312_range(data: &[u8]) -> bool {
     true
 }
 
-static VALID_RANGES: [&[(u8, u8)]; 128] = [
+pub(crate) static VALID_RANGES: [&[(u8, u8)]; 128] = [
     // 0x80 (index 0)
     &[],
     // 0x81 (index 1)
@@ -371,7 +373,9 @@ fn fallback(slice: &[u8], table: &[i32; 32768]) -> i32 {
     0
 }
 
-pub fn is_gb2312_simd(data: &[u8], table: &[i32; 32768]) -> bool {
+// Length of the prefix accepted by the SIMD kernel; always a character
+// boundary. Stops at the first block containing an error or at the tail.
+pub fn gb2312_valid_up_to(data: &[u8], table: &[i32; 32768]) -> usize {
     let len = data.len();
     let mut i = 0;
     while i + 15 < len {
@@ -392,26 +396,89 @@ pub fn is_gb2312_simd(data: &[u8], table: &[i32; 32768]) -> bool {
                 let cmp = _mm256_cmpeq_epi32(gathers, tools::ONE);
                 let mask = _mm256_movemask_epi8(cmp);
                 if mask != -1 {
-                    return false;
+                    return i;
                 }
                 i += 16;
             } else if mask == 0 {
                 i += 16;
             } else {
-                let fallback_result = fallback(&data[i..i + 16], &table);
+                let fallback_result = fallback(&data[i..i + 16], table);
                 if fallback_result == -1 {
-                    return false;
+                    return i;
                 } else {
                     i += 16 - fallback_result as usize;
                 }
             }
         }
     }
-    if i < len {
-        let fallback_result = fallback(&data[i..], &table);
-        if fallback_result == -1 {
-            return false;
+    i
+}
+
+pub fn is_gb2312_simd(data: &[u8], table: &[i32; 32768]) -> bool {
+    let i = gb2312_valid_up_to(data, table);
+    // a character cut off at the end is invalid as well
+    i == data.len() || fallback(&data[i..], table) == 0
+}
+
+pub fn gb2312_char_len(data: &[u8], i: usize) -> Result<usize, ErrorKind> {
+    let lead = data[i];
+    if lead <= 0x7F {
+        return Ok(1);
+    }
+    if !(0xA1..=0xFE).contains(&lead) {
+        return Err(ErrorKind::InvalidLead);
+    }
+    if i + 1 >= data.len() {
+        return Err(ErrorKind::Truncated);
+    }
+    let trail = data[i + 1];
+    if !(0xA1..=0xFE).contains(&trail) {
+        return Err(ErrorKind::InvalidTrail);
+    }
+    if !tools::in_ranges(VALID_RANGES[(lead - 0x80) as usize], trail) {
+        return Err(ErrorKind::Unassigned);
+    }
+    Ok(2)
+}
+
+// Unicode scalar of each valid pair, indexed like the validity table, as
+// encoding_rs decodes it; zero for invalid pairs.
+pub fn build_gb2312_unicode_table() -> [u16; 32768] {
+    tools::build_unicode_table(&build_gb2312_table(), GBK)
+}
+
+// Validates and decodes in one pass, with `tools::build_utf8_table` of the
+// table above. ASCII runs are copied in blocks; the output is the one of
+// encoding_rs for valid input.
+pub fn gb2312_decode_to_utf8(
+    data: &[u8],
+    utf8: &tools::Utf8Table,
+) -> Result<String, ValidationError> {
+    let mut out = tools::Utf8Output::for_input(data.len());
+    let mut i = 0;
+    while i < data.len() {
+        let lead = data[i];
+        if lead < 0x80 {
+            // the ASCII bytes from `i` on are not pushed yet
+            i += unsafe { out.push_ascii(&data[i..]) };
+            continue;
+        }
+        if lead >= 0xA1 && i + 1 < data.len() {
+            let entry = utf8[(lead - 0x80) as usize * 256 + data[i + 1] as usize];
+            if entry != 0 {
+                // a pair, consumed below
+                unsafe { out.push_entry(entry) };
+                i += 2;
+                continue;
+            }
         }
+        return Err(ValidationError {
+            encoding: Encoding::Gb2312,
+            offset: i,
+            kind: gb2312_char_len(data, i)
+                .err()
+                .unwrap_or(ErrorKind::Unassigned),
+        });
     }
-    true
+    Ok(out.into_string())
 }
@@ -1,5 +1,25 @@
+pub mod ambiguity;
+pub mod batch;
+pub mod column;
+pub mod context;
+pub mod detect;
+pub mod encode;
+pub mod encoding;
+pub mod error;
 pub mod euc_jp;
 pub mod euc_kr;
+pub mod file;
 pub mod gb18030;
 pub mod gb2312;
+pub mod model;
+pub mod multi;
+pub mod parallel;
+pub mod reader;
+pub mod scan;
+pub mod semval;
+pub mod shorttext;
+pub mod stream;
 pub mod tools;
+pub mod utf16;
+pub mod utf8;
+pub mod writer;
@@ -1,88 +1,502 @@
-use std::fs;
+use std::ffi::{OsStr, OsString};
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::{Mutex, OnceLock};
 
-use Janus::{euc_jp, euc_kr, tools};
-use Janus::{gb2312, gb18030};
-fn run_gb2312() {
-    let bytes = fs::read("dream_gb2312.txt").unwrap();
-    let result = gb2312::is_gb2312_iconv(&bytes);
-    println!("is_gb2312_iconv: {:?}", result);
+use Janus::context::error_context;
+use Janus::detect;
+use Janus::encoding::{Encoding, Strategy};
+use Janus::file::{self, Contents, FileError};
+use Janus::model::ModelScorer;
+use Janus::scan::{self, Glob, ScanOptions, Verdict};
+use Janus::shorttext;
 
-    let result = gb2312::is_gb2312_rs(&bytes);
-    println!("is_gb2312_rs: {:?}", result);
+const USAGE: &str = "\
+usage: janus validate --encoding ENCODING [--strategy STRATEGY] [FILE...]
+       janus detect [--json] [--min-confidence CONFIDENCE] [FILE...]
 
-    let result = gb2312::is_gb2312_range(&bytes);
-    println!("is_gb2312_range: {:?}", result);
+       janus scan --encoding ENCODING[,ENCODING...] [OPTION...] DIR...
 
-    let table = gb2312::build_gb2312_table();
-    let result = gb2312::is_gb2312_lookup(&bytes, &table);
-    println!("is_gb2312_lookup: {:?}", result);
+validate and detect read each FILE, or the standard input for '-' or no
+FILE.
 
-    let i32_table = tools::build_i32_table_from_bool(&table);
-    let result = gb2312::is_gb2312_simd(&bytes, &i32_table);
-    println!("is_gb2312_simd: {:?}", result);
+validate: prints one line per input with the offset and reason of the
+first error.
+  -e, --encoding ENCODING  gb2312, gb18030, euc-jp, euc-kr, utf-8, utf-16le
+                           or utf-16be
+  -s, --strategy STRATEGY  simd (default), lookup, range, fsm, iconv or rs;
+                           range and fsm are the same scalar validator,
+                           which lookup falls back to for gb18030 and the
+                           UTF encodings
+  -C, --context            show the line and column, a hex dump and the
+                           decoded text around the error
+
+detect: ranks the encodings that accept each input by the likelihood of
+the decoded text, and lists the errors of the others. Input without a
+non-ASCII byte is undecided.
+  -j, --json               one JSON object per input, one per line
+  -c, --min-confidence CONFIDENCE
+                           confidence the best reading needs, between 0
+                           and 1 (default 0.75)
+
+scan: validates the regular files under each DIR, and prints the files
+that no ENCODING accepts and a summary. --encoding may be repeated.
+  -e, --encoding, -s, --strategy as for validate
+  -i, --include GLOB       only files matching GLOB; may be repeated
+  -x, --exclude GLOB       skip files and directories matching GLOB; may
+                           be repeated
+  -L, --follow-symlinks    follow symbolic links, which are skipped
+                           otherwise
+  -t, --threads N          worker threads (default: one per CPU)
+  GLOB: '*', '?', '[a-z]' within a path component and '**/' across
+  directories. A GLOB without '/' matches the file name, otherwise the
+  path relative to DIR.
+
+  -h, --help               print this help
+
+exit status: 0 if every input is valid, or detected, 1 if one is not, 2
+on an I/O or usage error.
+";
+
+// Exit statuses; the highest one reached wins.
+const VALID: u8 = 0;
+const INVALID: u8 = 1;
+const TROUBLE: u8 = 2;
+
+// What the command line of a subcommand accepts: the long name, the short
+// name and whether the option takes a value.
+type Spec = [(&'static str, char, bool)];
+
+// A parsed command line: options in order, then operands.
+struct Args {
+    options: Vec<(&'static str, Option<String>)>,
+    operands: Vec<OsString>,
+}
+
+impl Args {
+    // Options take their value as `--name value`, `--name=value`, `-n value`
+    // or `-nvalue`; everything after `--` is an operand, as is `-`.
+    fn parse(mut args: impl Iterator<Item = OsString>, spec: &Spec) -> Result<Args, String> {
+        let mut parsed = Args {
+            options: Vec::new(),
+            operands: Vec::new(),
+        };
+        while let Some(arg) = args.next() {
+            let text = match arg.to_str() {
+                Some(text) if text.starts_with('-') && text != "-" => text,
+                _ => {
+                    parsed.operands.push(arg);
+                    continue;
+                }
+            };
+            if text == "--" {
+                parsed.operands.extend(args);
+                break;
+            }
+            let (found, inline) = match text.strip_prefix("--") {
+                Some(long) => {
+                    let (name, inline) = match long.split_once('=') {
+                        Some((name, value)) => (name, Some(value)),
+                        None => (long, None),
+                    };
+                    (spec.iter().find(|option| option.0 == name), inline)
+                }
+                None => {
+                    let mut chars = text[1..].chars();
+                    let short = chars.next();
+                    let rest = chars.as_str();
+                    let inline = if rest.is_empty() { None } else { Some(rest) };
+                    (spec.iter().find(|option| Some(option.1) == short), inline)
+                }
+            };
+            let &(name, _, takes_value) =
+                found.ok_or_else(|| format!("unknown option {}", text))?;
+            let value = match (takes_value, inline) {
+                (false, None) => None,
+                (false, Some(_)) => return Err(format!("option --{} takes no value", name)),
+                (true, Some(value)) => Some(value.to_string()),
+                (true, None) => match args.next().map(|value| value.into_string()) {
+                    Some(Ok(value)) => Some(value),
+                    _ => return Err(format!("option --{} needs a value", name)),
+                },
+            };
+            parsed.options.push((name, value));
+        }
+        Ok(parsed)
+    }
+
+    fn flag(&self, name: &str) -> bool {
+        self.options.iter().any(|option| option.0 == name)
+    }
+
+    fn values<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> {
+        self.options
+            .iter()
+            .filter(move |option| option.0 == name)
+            .filter_map(|option| option.1.as_deref())
+    }
+
+    // The last value given to the option.
+    fn value(&self, name: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .rev()
+            .find(|option| option.0 == name)
+            .and_then(|option| option.1.as_deref())
+    }
+}
+
+// The files to read; the standard input for `-` or none at all.
+fn inputs(operands: Vec<OsString>) -> Vec<OsString> {
+    if operands.is_empty() {
+        vec![OsString::from("-")]
+    } else {
+        operands
+    }
+}
+
+fn load(input: &OsStr) -> io::Result<Contents> {
+    if input == "-" {
+        let mut data = Vec::new();
+        io::stdin().lock().read_to_end(&mut data)?;
+        Ok(Contents::Read(data))
+    } else {
+        Contents::load(input)
+    }
 }
 
-fn run_gb18030() {
-    let bytes = fs::read("dream_gb2312.txt").unwrap();
+fn display_name(input: &OsStr) -> String {
+    if input == "-" {
+        "(standard input)".to_string()
+    } else {
+        input.to_string_lossy().into_owned()
+    }
+}
 
-    let result = gb18030::is_gb18030_iconv(&bytes);
-    println!("is_gb18030_iconv: {:?}", result);
+fn parse_encoding(name: &str) -> Result<Encoding, String> {
+    Encoding::from_name(name).ok_or_else(|| format!("unknown encoding {}", name))
+}
+
+fn parse_strategy(args: &Args) -> Result<Strategy, String> {
+    match args.value("strategy") {
+        Some(name) => Strategy::from_name(name).ok_or_else(|| format!("unknown strategy {}", name)),
+        None => Ok(Strategy::Simd),
+    }
+}
 
-    let result = gb18030::is_gb18030_rs(&bytes);
-    println!("is_gb18030_rs: {:?}", result);
+const VALIDATE_SPEC: &Spec = &[
+    ("encoding", 'e', true),
+    ("strategy", 's', true),
+    ("context", 'C', false),
+    ("help", 'h', false),
+];
 
-    let result = gb18030::is_gb18030_fsm(&bytes);
-    println!("is_gb18030_fsm: {:?}", result);
+fn validate_command(
+    args: impl Iterator<Item = OsString>,
+    out: &mut impl Write,
+) -> Result<u8, String> {
+    let args = Args::parse(args, VALIDATE_SPEC)?;
+    if args.flag("help") {
+        out.write_all(USAGE.as_bytes()).map_err(|e| e.to_string())?;
+        return Ok(VALID);
+    }
+    let encoding = parse_encoding(args.value("encoding").ok_or("missing --encoding")?)?;
+    let strategy = parse_strategy(&args)?;
+    let context = args.flag("context");
 
-    let result = gb18030::is_gb18030_simd(&bytes);
-    println!("is_gb18030_simd: {:?}", result);
+    let mut status = VALID;
+    for input in inputs(args.operands) {
+        // the excerpt needs the input in memory, even for the standard input
+        let mut contents = None;
+        let result = if context {
+            load(&input).map_err(FileError::Io).and_then(|loaded| {
+                file::validate_with(contents.insert(loaded).as_slice(), encoding, strategy)
+            })
+        } else if input == "-" {
+            file::validate_reader(io::stdin().lock(), encoding, strategy)
+        } else {
+            file::validate_file(&input, encoding, strategy)
+        };
+        let name = display_name(&input);
+        let line = match (result, &contents) {
+            (Ok(()), _) => format!("{}: valid", name),
+            (Err(FileError::Io(error)), _) => {
+                eprintln!("janus: {}: {}", name, error);
+                status = status.max(TROUBLE);
+                continue;
+            }
+            (Err(FileError::Invalid(error)), Some(contents)) => {
+                status = status.max(INVALID);
+                let context = error_context(contents.as_slice(), &error);
+                format!("{}: {}", name, context)
+            }
+            (Err(error), _) => {
+                status = status.max(INVALID);
+                format!("{}: {}", name, error)
+            }
+        };
+        // stop once the reader of the output has gone, as `head` does
+        if writeln!(out, "{}", line).is_err() {
+            return Ok(status.max(TROUBLE));
+        }
+    }
+    Ok(status)
 }
 
-fn run_jp() {
-    let bytes = fs::read("meian_eucjp.txt").unwrap();
+fn json_string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c if c < ' ' => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn json_list(items: impl Iterator<Item = String>) -> String {
+    format!("[{}]", items.collect::<Vec<_>>().join(","))
+}
 
-    let result = euc_jp::is_jp_iconv(&bytes);
-    println!("is_jp_iconv: {:?}", result);
+// Readings of `data` best first, then the encodings that reject it.
+fn detection(
+    name: &str,
+    data: &[u8],
+    min_confidence: f64,
+    json: bool,
+) -> (Option<Encoding>, String) {
+    let candidates = detect::detect(data);
+    let valid: Vec<Encoding> = candidates
+        .iter()
+        .filter(|candidate| candidate.is_valid())
+        .map(|candidate| candidate.encoding)
+        .collect();
+    let readings = SCORER
+        .get_or_init(ModelScorer::default)
+        .readings(data, &valid);
+    // ASCII-only input, empty input included, reads the same in every
+    // ASCII-compatible encoding and tells them nothing, as in `shorttext`
+    let best = readings
+        .first()
+        .filter(|reading| reading.confidence >= min_confidence && !data.is_ascii())
+        .map(|reading| reading.encodings[0]);
+    let errors = candidates.iter().filter_map(|candidate| candidate.error);
 
-    let result = euc_jp::is_jp_rs(&bytes);
-    println!("is_jp_rs: {:?}", result);
+    let names = |encodings: &[Encoding]| -> Vec<&str> {
+        encodings.iter().map(|encoding| encoding.name()).collect()
+    };
+    let output = if json {
+        let readings = readings.iter().map(|reading| {
+            format!(
+                "{{\"encodings\":{},\"confidence\":{:.6}}}",
+                json_list(names(&reading.encodings).into_iter().map(json_string)),
+                reading.confidence
+            )
+        });
+        let errors = errors.map(|error| {
+            format!(
+                "{{\"encoding\":{},\"offset\":{},\"reason\":{}}}",
+                json_string(error.encoding.name()),
+                error.offset,
+                json_string(error.kind.description())
+            )
+        });
+        format!(
+            "{{\"file\":{},\"best\":{},\"readings\":{},\"invalid\":{}}}\n",
+            json_string(name),
+            best.map_or("null".to_string(), |encoding| json_string(encoding.name())),
+            json_list(readings),
+            json_list(errors)
+        )
+    } else {
+        let mut output = match best {
+            Some(encoding) => format!("{}: {}\n", name, encoding),
+            None => format!("{}: undecided\n", name),
+        };
+        for reading in &readings {
+            output += &format!(
+                "  {:.4}  {}\n",
+                reading.confidence,
+                names(&reading.encodings).join(", ")
+            );
+        }
+        for error in errors {
+            output += &format!("  {}\n", error);
+        }
+        output
+    };
+    (best, output)
+}
+
+static SCORER: OnceLock<ModelScorer> = OnceLock::new();
 
-    let result = euc_jp::is_jp_range(&bytes);
-    println!("euc_jp_range: {:?}", result);
+const DETECT_SPEC: &Spec = &[
+    ("json", 'j', false),
+    ("min-confidence", 'c', true),
+    ("help", 'h', false),
+];
 
-    let table = euc_jp::build_jp_table();
-    let result = euc_jp::is_jp_lookup(&bytes, &table);
-    println!("is_jp_lookup: {:?}", result);
+fn detect_command(
+    args: impl Iterator<Item = OsString>,
+    out: &mut impl Write,
+) -> Result<u8, String> {
+    let args = Args::parse(args, DETECT_SPEC)?;
+    if args.flag("help") {
+        out.write_all(USAGE.as_bytes()).map_err(|e| e.to_string())?;
+        return Ok(VALID);
+    }
+    let min_confidence = match args.value("min-confidence") {
+        Some(value) => match value.parse::<f64>() {
+            Ok(confidence) if (0.0..=1.0).contains(&confidence) => confidence,
+            _ => return Err(format!("invalid confidence {}", value)),
+        },
+        None => shorttext::DEFAULT_MIN_CONFIDENCE,
+    };
+    let json = args.flag("json");
 
-    let i32_table = tools::build_i32_table_from_bool(&table);
-    let result = euc_jp::is_jp_simd(&bytes, &i32_table);
-    println!("is_jp_simd: {:?}", result);
+    let mut status = VALID;
+    for input in inputs(args.operands) {
+        let name = display_name(&input);
+        let contents = match load(&input) {
+            Ok(contents) => contents,
+            Err(error) => {
+                eprintln!("janus: {}: {}", name, error);
+                status = status.max(TROUBLE);
+                continue;
+            }
+        };
+        let (best, output) = detection(&name, contents.as_slice(), min_confidence, json);
+        if best.is_none() {
+            status = status.max(INVALID);
+        }
+        if out.write_all(output.as_bytes()).is_err() {
+            return Ok(status.max(TROUBLE));
+        }
+    }
+    Ok(status)
 }
 
-fn run_kr() {
-    let bytes = fs::read("heartless_euckr.txt").unwrap();
+const SCAN_SPEC: &Spec = &[
+    ("encoding", 'e', true),
+    ("strategy", 's', true),
+    ("include", 'i', true),
+    ("exclude", 'x', true),
+    ("follow-symlinks", 'L', false),
+    ("threads", 't', true),
+    ("help", 'h', false),
+];
 
-    let result = euc_kr::is_kr_iconv(&bytes);
-    println!("is_kr_iconv: {:?}", result);
+fn scan_command(args: impl Iterator<Item = OsString>, out: &mut impl Write) -> Result<u8, String> {
+    let args = Args::parse(args, SCAN_SPEC)?;
+    if args.flag("help") {
+        out.write_all(USAGE.as_bytes()).map_err(|e| e.to_string())?;
+        return Ok(VALID);
+    }
+    let encodings = args
+        .values("encoding")
+        .flat_map(|names| names.split(','))
+        .map(parse_encoding)
+        .collect::<Result<Vec<Encoding>, String>>()?;
+    if encodings.is_empty() {
+        return Err("missing --encoding".to_string());
+    }
+    if args.operands.is_empty() {
+        return Err("missing directory".to_string());
+    }
+    let mut options = ScanOptions::new(encodings);
+    options.strategy = parse_strategy(&args)?;
+    options.include = args.values("include").map(Glob::new).collect();
+    options.exclude = args.values("exclude").map(Glob::new).collect();
+    options.follow_symlinks = args.flag("follow-symlinks");
+    if let Some(threads) = args.value("threads") {
+        options.threads = threads
+            .parse()
+            .map_err(|_| format!("invalid thread count {}", threads))?;
+    }
 
-    let result = euc_kr::is_kr_rs(&bytes);
-    println!("is_kr_rs: {:?}", result);
+    let roots: Vec<PathBuf> = args.operands.into_iter().map(PathBuf::from).collect();
+    let failures = Mutex::new(Vec::new());
+    let summary = scan::scan(&roots, &options, |file| {
+        let line = match &file.verdict {
+            Verdict::Valid(_) => return,
+            Verdict::Invalid(errors) => {
+                let errors: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+                format!("invalid  {}: {}", file.path.display(), errors.join("; "))
+            }
+            Verdict::Error(error) => format!("error    {}: {}", file.path.display(), error),
+        };
+        failures.lock().unwrap().push((file.path.clone(), line));
+    });
+    let mut failures = failures.into_inner().unwrap();
+    failures.sort();
 
-    let table = euc_kr::build_kr_table();
-    let result = euc_kr::is_kr_lookup(&bytes, &table);
-    println!("is_kr_lookup: {:?}", result);
+    let valid: Vec<String> = options
+        .encodings
+        .iter()
+        .zip(&summary.valid)
+        .map(|(encoding, count)| format!("{} {}", encoding, count))
+        .collect();
+    let mut output = String::new();
+    for (_, line) in &failures {
+        output += line;
+        output.push('\n');
+    }
+    output += &format!(
+        "scanned {} files, {:.1} MiB in {:.2} s ({:.1} MiB/s)\n",
+        summary.files(),
+        summary.bytes as f64 / MIB,
+        summary.elapsed.as_secs_f64(),
+        summary.throughput() / MIB
+    );
+    output += &format!(
+        "  valid    {:>8}  ({})\n",
+        summary.valid.iter().sum::<usize>(),
+        valid.join(", ")
+    );
+    output += &format!("  invalid  {:>8}\n", summary.invalid);
+    output += &format!("  error    {:>8}\n", summary.errors);
+    output += &format!("  skipped  {:>8}\n", summary.skipped);
 
-    let i32_table = tools::build_i32_table_from_bool(&table);
-    let result = euc_kr::is_kr_simd(&bytes, &i32_table);
-    println!("is_kr_simd: {:?}", result);
+    let status = if summary.errors > 0 {
+        TROUBLE
+    } else if summary.invalid > 0 {
+        INVALID
+    } else {
+        VALID
+    };
+    if out.write_all(output.as_bytes()).is_err() {
+        return Ok(TROUBLE);
+    }
+    Ok(status)
 }
 
-fn main() {
-    run_gb2312();
-    println!("--------------");
-    run_gb18030();
-    println!("--------------");
-    run_kr();
-    println!("--------------");
-    run_jp();
+const MIB: f64 = 1024.0 * 1024.0;
+
+fn main() -> ExitCode {
+    let mut args = std::env::args_os().skip(1);
+    let mut out = io::stdout().lock();
+    let result = match args.next() {
+        Some(command) if command == "validate" => validate_command(args, &mut out),
+        Some(command) if command == "detect" => detect_command(args, &mut out),
+        Some(command) if command == "scan" => scan_command(args, &mut out),
+        Some(command) if command == "-h" || command == "--help" || command == "help" => out
+            .write_all(USAGE.as_bytes())
+            .map_err(|e| e.to_string())
+            .map(|_| VALID),
+        Some(command) => Err(format!("unknown command {}", command.to_string_lossy())),
+        None => Err("missing command".to_string()),
+    };
+    match result {
+        Ok(status) => ExitCode::from(status),
+        Err(message) => {
+            eprintln!("janus: {}\nrun 'janus --help' for usage", message);
+            ExitCode::from(TROUBLE)
+        }
+    }
 }
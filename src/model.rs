@@ -0,0 +1,320 @@
+use crate::encoding::Encoding;
+use crate::error::ValidationError;
+use crate::semval::SemanticScorer;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::OnceLock;
+
+const MAGIC: &str = "janus-model 1";
+
+// Add-alpha smoothing over the Basic Multilingual Plane, so unseen
+// characters are rare but never impossible.
+const ALPHA: f64 = 0.5;
+const VOCABULARY: f64 = 65536.0;
+
+// Counts of the non-ASCII characters of a corpus. The counts are keyed by
+// the decoded character, so a model trained on GB2312 text also scores the
+// same text in GB18030 or UTF-8.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FrequencyModel {
+    encoding: Encoding,
+    counts: HashMap<char, u64>,
+    total: u64,
+}
+
+impl FrequencyModel {
+    pub fn new(encoding: Encoding) -> Self {
+        FrequencyModel {
+            encoding,
+            counts: HashMap::new(),
+            total: 0,
+        }
+    }
+
+    pub fn from_corpus(encoding: Encoding, data: &[u8]) -> Result<Self, ValidationError> {
+        let mut model = FrequencyModel::new(encoding);
+        model.train(data)?;
+        Ok(model)
+    }
+
+    // Adds a corpus in the model's encoding. Invalid input is rejected as a
+    // whole so that a wrongly labelled file does not pollute the counts.
+    pub fn train(&mut self, data: &[u8]) -> Result<(), ValidationError> {
+        self.encoding.validate(data)?;
+        let (text, _) = self
+            .encoding
+            .rs_encoding()
+            .decode_without_bom_handling(data);
+        self.add_text(&text);
+        Ok(())
+    }
+
+    pub fn add_text(&mut self, text: &str) {
+        for c in text.chars().filter(|c| !c.is_ascii()) {
+            *self.counts.entry(c).or_insert(0) += 1;
+            self.total += 1;
+        }
+    }
+
+    // The encoding of the training corpus.
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    pub fn count(&self, c: char) -> u64 {
+        self.counts.get(&c).copied().unwrap_or(0)
+    }
+
+    // Number of distinct characters seen.
+    pub fn len(&self) -> usize {
+        self.counts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+
+    // Characters with their counts, most frequent first.
+    pub fn chars(&self) -> Vec<(char, u64)> {
+        let mut chars: Vec<(char, u64)> = self.counts.iter().map(|(&c, &n)| (c, n)).collect();
+        chars.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        chars
+    }
+
+    pub fn log_probability(&self, c: char) -> f64 {
+        ((self.count(c) as f64 + ALPHA) / (self.total as f64 + ALPHA * VOCABULARY)).ln()
+    }
+
+    // Sum of the log-probabilities of the non-ASCII characters of `text`.
+    pub fn log_likelihood(&self, text: &str) -> f64 {
+        text.chars()
+            .filter(|c| !c.is_ascii())
+            .map(|c| self.log_probability(c))
+            .sum()
+    }
+
+    // Text format: a header, the encoding, the total, then one
+    // `<code point in hex> <count>` line per character, most frequent first.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "{}", MAGIC)?;
+        writeln!(writer, "encoding {}", self.encoding)?;
+        writeln!(writer, "total {}", self.total)?;
+        for (c, n) in self.chars() {
+            writeln!(writer, "{:X} {}", c as u32, n)?;
+        }
+        writer.flush()
+    }
+
+    pub fn read_from<R: BufRead>(reader: R) -> io::Result<Self> {
+        let mut lines = reader.lines();
+        let mut next_line = || -> io::Result<String> {
+            lines
+                .next()
+                .unwrap_or_else(|| Err(invalid_data("unexpected end of model")))
+        };
+        if next_line()? != MAGIC {
+            return Err(invalid_data("not a janus model"));
+        }
+        let line = next_line()?;
+        let encoding = line
+            .strip_prefix("encoding ")
+            .and_then(Encoding::from_name)
+            .ok_or_else(|| invalid_data("missing or unknown encoding"))?;
+        let line = next_line()?;
+        let total: u64 = line
+            .strip_prefix("total ")
+            .and_then(|total| total.parse().ok())
+            .ok_or_else(|| invalid_data("missing total"))?;
+
+        let mut model = FrequencyModel::new(encoding);
+        for line in lines {
+            let line = line?;
+            if line.is_empty() {
+                continue;
+            }
+            let (code, count) = line
+                .split_once(' ')
+                .ok_or_else(|| invalid_data("malformed count line"))?;
+            let c = u32::from_str_radix(code, 16)
+                .ok()
+                .and_then(char::from_u32)
+                .ok_or_else(|| invalid_data("invalid code point"))?;
+            let count: u64 = count.parse().map_err(|_| invalid_data("invalid count"))?;
+            model.counts.insert(c, count);
+            model.total += count;
+        }
+        if model.total != total {
+            return Err(invalid_data("counts do not add up to the total"));
+        }
+        Ok(model)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.write_to(BufWriter::new(File::create(path)?))
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        FrequencyModel::read_from(BufReader::new(File::open(path)?))
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+// Trained on the bundled novels: dream_gb2312.txt, heartless_euckr.txt and
+// meian_eucjp.txt. Regenerate with `cargo run --example build_models`.
+static DEFAULT_MODEL_SOURCES: [&str; 3] = [
+    include_str!("../models/gb2312.model"),
+    include_str!("../models/euc_kr.model"),
+    include_str!("../models/euc_jp.model"),
+];
+
+static DEFAULT_MODELS: OnceLock<Vec<FrequencyModel>> = OnceLock::new();
+
+pub fn default_models() -> &'static [FrequencyModel] {
+    DEFAULT_MODELS.get_or_init(|| {
+        DEFAULT_MODEL_SOURCES
+            .iter()
+            .map(|source| FrequencyModel::read_from(source.as_bytes()).expect("bundled model"))
+            .collect()
+    })
+}
+
+// Scores a candidate with the models trained on its encoding; encodings
+// without one (GB18030, the UTF encodings) take the best of all models.
+// The score is the log-likelihood per non-ASCII byte, so that decodings
+// into characters of different widths stay comparable.
+#[derive(Clone, Debug)]
+pub struct ModelScorer {
+    models: Vec<FrequencyModel>,
+}
+
+impl ModelScorer {
+    pub fn new(models: Vec<FrequencyModel>) -> Self {
+        ModelScorer { models }
+    }
+
+    pub fn models(&self) -> &[FrequencyModel] {
+        &self.models
+    }
+}
+
+impl Default for ModelScorer {
+    fn default() -> Self {
+        ModelScorer::new(default_models().to_vec())
+    }
+}
+
+impl ModelScorer {
+    fn has_model(&self, encoding: Encoding) -> bool {
+        self.models.iter().any(|model| model.encoding == encoding)
+    }
+
+    fn log_likelihood(&self, encoding: Encoding, text: &str) -> f64 {
+        let own = self.has_model(encoding);
+        self.models
+            .iter()
+            .filter(|model| !own || model.encoding == encoding)
+            .map(|model| model.log_likelihood(text))
+            .fold(f64::NEG_INFINITY, f64::max)
+    }
+
+    // The distinct decodings of `data` in `encodings`, which all accept it
+    // structurally, most likely first. Encodings that decode `data` to the
+    // same text, such as GB2312 and GB18030, or every ASCII-compatible
+    // encoding for ASCII input, share one reading.
+    pub fn readings(&self, data: &[u8], encodings: &[Encoding]) -> Vec<Reading> {
+        let mut texts: Vec<String> = Vec::new();
+        let mut groups: Vec<Vec<Encoding>> = Vec::new();
+        for &encoding in encodings {
+            let (text, _) = encoding.rs_encoding().decode_without_bom_handling(data);
+            if let Some(same) = texts.iter().position(|seen| *seen == text) {
+                groups[same].push(encoding);
+            } else {
+                texts.push(text.into_owned());
+                groups.push(vec![encoding]);
+            }
+        }
+
+        // The same text may read better with one encoding's model than with
+        // another's, as kana do with EUC-JP's rather than GB2312's: a
+        // reading takes the best score of its encodings, and the encoding
+        // whose own model gives it leads.
+        let mut readings: Vec<Reading> = Vec::new();
+        for (text, mut encodings) in texts.iter().zip(groups) {
+            let scores: Vec<f64> = encodings
+                .iter()
+                .map(|&encoding| self.log_likelihood(encoding, text))
+                .collect();
+            let lead = (0..encodings.len())
+                .filter(|&i| self.has_model(encodings[i]))
+                .reduce(|best, i| if scores[i] > scores[best] { i } else { best });
+            if let Some(lead) = lead {
+                let encoding = encodings.remove(lead);
+                encodings.insert(0, encoding);
+            }
+            readings.push(Reading {
+                encodings,
+                log_likelihood: scores.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+                confidence: 0.0,
+            });
+        }
+
+        // softmax over the log-likelihoods, with equal priors per reading;
+        // without models every reading is as likely
+        let max = readings
+            .iter()
+            .map(|reading| reading.log_likelihood)
+            .fold(f64::NEG_INFINITY, f64::max);
+        let weight = |reading: &Reading| {
+            if max.is_finite() {
+                (reading.log_likelihood - max).exp()
+            } else {
+                1.0
+            }
+        };
+        let sum: f64 = readings.iter().map(weight).sum();
+        for reading in &mut readings {
+            reading.confidence = weight(reading) / sum;
+        }
+        readings.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+        readings
+    }
+}
+
+impl SemanticScorer for ModelScorer {
+    // Mean log-likelihood of the non-ASCII characters of the decoded text.
+    // Text without any is only neutral when it is the input itself: ASCII
+    // code units read out of UTF-16 give the models nothing to go on.
+    fn score(&self, encoding: Encoding, data: &[u8]) -> f64 {
+        let (text, _) = encoding.rs_encoding().decode_without_bom_handling(data);
+        let chars = text.chars().filter(|c| !c.is_ascii()).count();
+        if chars == 0 {
+            return if text.as_bytes() == data {
+                0.0
+            } else {
+                f64::NEG_INFINITY
+            };
+        }
+        self.log_likelihood(encoding, &text) / chars as f64
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Reading {
+    // the encoding whose own model reads the text best first, the others
+    // in the order given to `readings`
+    pub encodings: Vec<Encoding>,
+    // of the non-ASCII characters of the decoded text, with the best of
+    // the models of `encodings`
+    pub log_likelihood: f64,
+    // posterior probability among the readings; they add up to 1
+    pub confidence: f64,
+}
@@ -0,0 +1,159 @@
+use crate::encoding::Encoding;
+use crate::{euc_jp, euc_kr, gb2312, tools};
+use std::arch::x86_64::*;
+use std::sync::OnceLock;
+
+// One validity bit per encoding in each entry of the combined table.
+pub const GB2312: u8 = 1 << 0;
+pub const EUC_KR: u8 = 1 << 1;
+pub const EUC_JP: u8 = 1 << 2;
+pub const ALL: u8 = GB2312 | EUC_KR | EUC_JP;
+
+// Entries are indexed like the single-encoding tables, by
+// (lead - 0x80) * 256 + trail. Row 0x8E only carries the EUC-JP bit.
+pub fn build_multi_table() -> [i32; 32768] {
+    let gb2312 = gb2312::build_gb2312_table();
+    let kr = euc_kr::build_kr_table();
+    let jp = euc_jp::build_jp_table();
+    let mut table = [0; 32768];
+    for i in 0..32768 {
+        let mut bits = 0;
+        if gb2312[i] {
+            bits |= GB2312;
+        }
+        if kr[i] {
+            bits |= EUC_KR;
+        }
+        if jp[i] {
+            bits |= EUC_JP;
+        }
+        table[i] = bits as i32;
+    }
+    table
+}
+
+static MULTI_TABLE: OnceLock<Box<[i32; 32768]>> = OnceLock::new();
+
+fn multi_table() -> &'static [i32; 32768] {
+    MULTI_TABLE.get_or_init(|| Box::new(build_multi_table()))
+}
+
+// The encodings in `mask`, in the order of `Encoding::ALL`.
+pub fn encodings(mask: u8) -> Vec<Encoding> {
+    [
+        (GB2312, Encoding::Gb2312),
+        (EUC_KR, Encoding::EucKr),
+        (EUC_JP, Encoding::EucJp),
+    ]
+    .into_iter()
+    .filter(|(bit, _)| mask & bit != 0)
+    .map(|(_, encoding)| encoding)
+    .collect()
+}
+
+// Walks `slice` while the remaining encodings agree on character
+// boundaries, clearing bits of `mask` as pairs fail. Returns where it
+// stopped: `slice.len()`, a lead byte cut off at the end, a 0x8F lead
+// (three bytes in EUC-JP only), or the pair that cleared the last bit.
+fn multi_fallback(slice: &[u8], table: &[i32; 32768], mask: &mut u8) -> usize {
+    let mut i = 0;
+    while i < slice.len() {
+        let lead = slice[i];
+        if lead <= 0x7F {
+            i += 1;
+        } else if lead == 0x8F {
+            return i;
+        } else {
+            if i + 1 >= slice.len() {
+                return i;
+            }
+            let index = (lead as usize - 0x80) * 256 + slice[i + 1] as usize;
+            *mask &= table[index] as u8;
+            if *mask == 0 {
+                return i;
+            }
+            i += 2;
+        }
+    }
+    i
+}
+
+// Once a single encoding is left, its own kernel finishes the input.
+fn finish_single(data: &[u8], mask: u8) -> u8 {
+    let valid = match mask {
+        GB2312 => Encoding::Gb2312.is_valid(data),
+        EUC_KR => Encoding::EucKr.is_valid(data),
+        EUC_JP => Encoding::EucJp.is_valid(data),
+        _ => return 0,
+    };
+    if valid { mask } else { 0 }
+}
+
+static EIGHT_F: __m128i = unsafe { std::mem::transmute([0x8Fu8 as i8; 16]) };
+
+// Validates `data` as GB2312, EUC-KR and EUC-JP in one pass over a combined
+// table and returns the mask of the encodings that accept all of it.
+pub fn multi_valid_mask(data: &[u8], table: &[i32; 32768]) -> u8 {
+    let len = data.len();
+    let mut mask = ALL;
+    let mut i = 0;
+    while i + 15 < len {
+        if mask.count_ones() == 1 {
+            return finish_single(&data[i..], mask);
+        }
+        let chunk = unsafe { _mm_loadu_si128(data[i..].as_ptr() as *const __m128i) };
+        let high = unsafe { _mm_movemask_epi8(chunk) };
+        let has_8f = unsafe { _mm_movemask_epi8(_mm_cmpeq_epi8(chunk, EIGHT_F)) } != 0;
+        if high == 0xFFFF && !has_8f {
+            unsafe {
+                let leads = _mm_shuffle_epi8(chunk, tools::LEAD_BYTE_MASK);
+                let leads = _mm_cvtepu8_epi16(leads);
+                let trails = _mm_shuffle_epi8(chunk, tools::TRAIL_BYTE_MASK);
+                let trails = _mm_cvtepu8_epi16(trails);
+                let sub_leads = _mm_sub_epi16(leads, tools::EIGHTY);
+                let shift_leads = _mm_slli_epi16::<8>(sub_leads);
+                let indices = _mm_or_si128(shift_leads, trails);
+                let indices = _mm256_cvtepu16_epi32(indices);
+                let gathers = _mm256_i32gather_epi32::<4>(table.as_ptr(), indices);
+                // AND the eight entries together
+                let bits = _mm_and_si128(
+                    _mm256_castsi256_si128(gathers),
+                    _mm256_extracti128_si256::<1>(gathers),
+                );
+                let bits = _mm_and_si128(bits, _mm_shuffle_epi32::<0x4E>(bits));
+                let bits = _mm_and_si128(bits, _mm_shuffle_epi32::<0xB1>(bits));
+                mask &= _mm_cvtsi128_si32(bits) as u8;
+            }
+            if mask == 0 {
+                return 0;
+            }
+            i += 16;
+        } else if high == 0 {
+            // all ASCII
+            i += 16;
+        } else {
+            let stop = multi_fallback(&data[i..i + 16], table, &mut mask);
+            if mask == 0 {
+                return 0;
+            }
+            if stop < 16 && data[i + stop] == 0x8F {
+                return finish_single(&data[i + stop..], mask & EUC_JP);
+            }
+            // a pair crossing the block boundary starts the next block
+            i += stop;
+        }
+    }
+    let stop = i + multi_fallback(&data[i..], table, &mut mask);
+    if mask == 0 || stop == len {
+        return mask;
+    }
+    if data[stop] == 0x8F {
+        return finish_single(&data[stop..], mask & EUC_JP);
+    }
+    // a lead byte cut off at the end
+    0
+}
+
+pub fn multi_valid_encodings(data: &[u8]) -> Vec<Encoding> {
+    encodings(multi_valid_mask(data, multi_table()))
+}
@@ -0,0 +1,126 @@
+use crate::encoding::Encoding;
+use crate::error::ValidationError;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+// Chunks smaller than this are not worth a thread.
+const MIN_CHUNK: usize = 1 << 20;
+// Workers check for an earlier failure between blocks of this size.
+const BLOCK: usize = 1 << 20;
+
+// First offset in `data[from..limit]` that starts a character whenever the
+// input before it is valid. A byte alone does not tell a lead from a trail
+// in the EUC encodings, so they resynchronise on bytes that never appear
+// inside a character:
+// - GB2312, EUC-KR, EUC-JP: ASCII, since trail bytes are 0xA1-0xFE;
+// - GB18030: 0x00-0x2F, 0x3A-0x3F and 0x7F, which are neither two-byte
+//   trails nor the digits of a four-byte sequence;
+// - UTF-8: any byte but a continuation byte;
+// - UTF-16: an even offset that is not a low surrogate.
+pub fn resync(encoding: Encoding, data: &[u8], from: usize, limit: usize) -> Option<usize> {
+    let limit = limit.min(data.len());
+    match encoding {
+        Encoding::Gb2312 | Encoding::EucJp | Encoding::EucKr => {
+            (from..limit).find(|&i| data[i] < 0x80)
+        }
+        Encoding::Gb18030 => {
+            (from..limit).find(|&i| matches!(data[i], 0x00..=0x2F | 0x3A..=0x3F | 0x7F))
+        }
+        Encoding::Utf8 => (from..limit).find(|&i| data[i] & 0xC0 != 0x80),
+        Encoding::Utf16Le | Encoding::Utf16Be => {
+            let high = if encoding == Encoding::Utf16Le { 1 } else { 0 };
+            let mut i = from + from % 2;
+            while i < limit {
+                if i + 1 >= data.len() || data[i + high] & 0xFC != 0xDC {
+                    return Some(i);
+                }
+                i += 2;
+            }
+            None
+        }
+    }
+}
+
+// Splits `data` at resynchronised boundaries into at most `chunks` pieces.
+fn boundaries(encoding: Encoding, data: &[u8], chunks: usize) -> Vec<usize> {
+    let size = data.len().div_ceil(chunks);
+    let mut bounds = vec![0];
+    let mut nominal = size;
+    while nominal < data.len() {
+        let next = nominal + size;
+        if let Some(bound) = resync(encoding, data, nominal, next)
+            && bound > *bounds.last().unwrap()
+            && bound < data.len()
+        {
+            bounds.push(bound);
+        }
+        nominal = next;
+    }
+    bounds.push(data.len());
+    bounds
+}
+
+// Same result as `encoding.validate(data)`, spread over `threads` threads;
+// zero uses the available parallelism.
+pub fn validate_parallel(
+    data: &[u8],
+    encoding: Encoding,
+    threads: usize,
+) -> Result<(), ValidationError> {
+    let threads = if threads == 0 {
+        thread::available_parallelism().map_or(1, |n| n.get())
+    } else {
+        threads
+    };
+    let chunks = threads.min(data.len() / MIN_CHUNK).max(1);
+    if chunks == 1 {
+        return encoding.validate(data);
+    }
+
+    let bounds = boundaries(encoding, data, chunks);
+    // index of the first chunk known to fail; later chunks may stop early
+    let first_failed = AtomicUsize::new(usize::MAX);
+    let validate_chunk = |index: usize| -> Option<Result<usize, ValidationError>> {
+        let (start, end) = (bounds[index], bounds[index + 1]);
+        let mut i = start;
+        while i < end {
+            if first_failed.load(Ordering::Relaxed) < index {
+                return None;
+            }
+            match encoding.validate_span(data, i, (i + BLOCK).min(end)) {
+                Ok(next) => i = next,
+                Err(error) => {
+                    first_failed.fetch_min(index, Ordering::Relaxed);
+                    return Some(Err(error));
+                }
+            }
+        }
+        Some(Ok(i))
+    };
+
+    let results: Vec<Option<Result<usize, ValidationError>>> = thread::scope(|scope| {
+        let workers: Vec<_> = (1..bounds.len() - 1)
+            .map(|index| scope.spawn(move || validate_chunk(index)))
+            .collect();
+        let mut results = vec![validate_chunk(0)];
+        results.extend(workers.into_iter().map(|worker| worker.join().unwrap()));
+        results
+    });
+
+    // Chunks are only trusted in order: a chunk started on a character
+    // boundary if every chunk before it was valid and ended exactly there.
+    for (index, result) in results.into_iter().enumerate() {
+        match result {
+            Some(Ok(end)) if end == bounds[index + 1] => continue,
+            Some(Ok(end)) => return encoding.validate_span(data, end, data.len()).map(|_| ()),
+            Some(Err(error)) => return Err(error),
+            // stopped because an earlier chunk failed
+            None => unreachable!("an earlier chunk has failed"),
+        }
+    }
+    Ok(())
+}
+
+pub fn is_valid_parallel(data: &[u8], encoding: Encoding, threads: usize) -> bool {
+    validate_parallel(data, encoding, threads).is_ok()
+}
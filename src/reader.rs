@@ -0,0 +1,125 @@
+use crate::encoding::Encoding;
+use crate::error::ValidationError;
+use crate::stream::StreamValidator;
+use std::io::{self, BufRead, Read};
+
+const DEFAULT_CAPACITY: usize = 64 * 1024;
+
+// Passes the bytes of `inner` through unchanged while validating them. The
+// bytes before an error are still delivered; the read after them fails
+// with an `io::ErrorKind::InvalidData` error wrapping the
+// `ValidationError`, see `validation_error`.
+pub struct ValidatingReader<R> {
+    inner: R,
+    validator: StreamValidator,
+    buf: Box<[u8]>,
+    pos: usize,
+    filled: usize,
+    // bytes read from `inner` so far
+    read: usize,
+    error: Option<ValidationError>,
+}
+
+impl<R: Read> ValidatingReader<R> {
+    pub fn new(inner: R, encoding: Encoding) -> Self {
+        ValidatingReader::with_capacity(DEFAULT_CAPACITY, inner, encoding)
+    }
+
+    pub fn with_capacity(capacity: usize, inner: R, encoding: Encoding) -> Self {
+        ValidatingReader {
+            inner,
+            validator: StreamValidator::new(encoding),
+            buf: vec![0; capacity].into_boxed_slice(),
+            pos: 0,
+            filled: 0,
+            read: 0,
+            error: None,
+        }
+    }
+
+    pub fn encoding(&self) -> Encoding {
+        self.validator.encoding()
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    // Reads from `inner` into `out` and returns how many of the bytes read
+    // may be handed out. Zero means end of input or an error.
+    fn read_checked(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if let Some(error) = self.error {
+            return Err(error.into());
+        }
+        let n = self.inner.read(out)?;
+        if n == 0 {
+            return match self.validator.finish() {
+                Ok(()) => Ok(0),
+                Err(error) => {
+                    self.error = Some(error);
+                    Err(error.into())
+                }
+            };
+        }
+        let start = self.read;
+        self.read += n;
+        match self.validator.feed(&out[..n]) {
+            Ok(()) => Ok(n),
+            Err(error) => {
+                self.error = Some(error);
+                // hand out the bytes before the error first
+                let valid = error.offset.saturating_sub(start).min(n);
+                if valid == 0 {
+                    Err(error.into())
+                } else {
+                    Ok(valid)
+                }
+            }
+        }
+    }
+}
+
+// The validation failure behind an error returned by a `ValidatingReader`
+// or `ValidatingWriter`.
+pub fn validation_error(error: &io::Error) -> Option<&ValidationError> {
+    error.get_ref()?.downcast_ref::<ValidationError>()
+}
+
+impl<R: Read> Read for ValidatingReader<R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        // large reads bypass the internal buffer
+        if self.pos == self.filled && out.len() >= self.buf.len() {
+            return self.read_checked(out);
+        }
+        let available = self.fill_buf()?;
+        let n = available.len().min(out.len());
+        out[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: Read> BufRead for ValidatingReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.pos == self.filled {
+            let mut buf = std::mem::take(&mut self.buf);
+            let result = self.read_checked(&mut buf);
+            self.buf = buf;
+            self.filled = result?;
+            self.pos = 0;
+        }
+        Ok(&self.buf[self.pos..self.filled])
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.pos = (self.pos + amount).min(self.filled);
+    }
+}
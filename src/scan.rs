@@ -0,0 +1,391 @@
+use crate::encoding::{Encoding, Strategy};
+use crate::file::{self, Contents, FileError};
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Shell-style patterns: `*` matches within a path component, `**/` any
+// number of whole directories, `?` one character but `/`, `[a-z]` and
+// `[!a-z]` a class. A `[` without its `]` is literal, as in fnmatch.
+// Patterns without a `/` match the file name at any depth; the others
+// match the path relative to the scanned directory.
+#[derive(Clone, Debug)]
+pub struct Glob {
+    tokens: Vec<Token>,
+    anchored: bool,
+}
+
+#[derive(Clone, Debug)]
+enum Token {
+    Literal(char),
+    One,
+    Star,
+    // `**/`: nothing, or anything ending in `/`
+    AnyDirs,
+    // `**` elsewhere: anything
+    AnyPath,
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+}
+
+impl Glob {
+    pub fn new(pattern: &str) -> Glob {
+        let chars: Vec<char> = pattern.chars().collect();
+        let mut tokens = Vec::new();
+        let mut i = 0;
+        while i < chars.len() {
+            match chars[i] {
+                '*' if chars.get(i + 1) == Some(&'*') => {
+                    if chars.get(i + 2) == Some(&'/') {
+                        tokens.push(Token::AnyDirs);
+                        i += 3;
+                    } else {
+                        tokens.push(Token::AnyPath);
+                        i += 2;
+                    }
+                    continue;
+                }
+                '*' => tokens.push(Token::Star),
+                '?' => tokens.push(Token::One),
+                '[' => {
+                    if let Some((class, next)) = Glob::class(&chars, i + 1) {
+                        tokens.push(class);
+                        i = next;
+                        continue;
+                    }
+                    tokens.push(Token::Literal('['));
+                }
+                c => tokens.push(Token::Literal(c)),
+            }
+            i += 1;
+        }
+        Glob {
+            tokens,
+            anchored: pattern.contains('/'),
+        }
+    }
+
+    // The class starting after a `[`, and the index after its `]`.
+    fn class(chars: &[char], start: usize) -> Option<(Token, usize)> {
+        let mut i = start;
+        let negated = matches!(chars.get(i), Some('!' | '^'));
+        if negated {
+            i += 1;
+        }
+        let mut ranges = Vec::new();
+        // a `]` right after the opening is a member
+        let first = i;
+        while i < chars.len() && (chars[i] != ']' || i == first) {
+            if i + 2 < chars.len() && chars[i + 1] == '-' && chars[i + 2] != ']' {
+                ranges.push((chars[i], chars[i + 2]));
+                i += 3;
+            } else {
+                ranges.push((chars[i], chars[i]));
+                i += 1;
+            }
+        }
+        if i >= chars.len() {
+            return None;
+        }
+        Some((Token::Class { negated, ranges }, i + 1))
+    }
+
+    // `path` is relative to the scanned directory, with `/` separators.
+    pub fn matches(&self, path: &str) -> bool {
+        let text = if self.anchored {
+            path
+        } else {
+            path.rsplit('/').next().unwrap_or(path)
+        };
+        let text: Vec<char> = text.chars().collect();
+        match_tokens(&self.tokens, &text)
+    }
+}
+
+fn match_tokens(tokens: &[Token], text: &[char]) -> bool {
+    let Some((token, rest)) = tokens.split_first() else {
+        return text.is_empty();
+    };
+    match token {
+        Token::Literal(c) => text.first() == Some(c) && match_tokens(rest, &text[1..]),
+        Token::One => text.first().is_some_and(|&c| c != '/') && match_tokens(rest, &text[1..]),
+        Token::Class { negated, ranges } => {
+            text.first().is_some_and(|&c| {
+                c != '/' && ranges.iter().any(|&(low, high)| low <= c && c <= high) != *negated
+            }) && match_tokens(rest, &text[1..])
+        }
+        Token::Star => (0..=text.len())
+            .take_while(|&i| i == 0 || text[i - 1] != '/')
+            .any(|i| match_tokens(rest, &text[i..])),
+        Token::AnyDirs => {
+            match_tokens(rest, text)
+                || (0..text.len())
+                    .filter(|&i| text[i] == '/')
+                    .any(|i| match_tokens(rest, &text[i + 1..]))
+        }
+        Token::AnyPath => (0..=text.len()).any(|i| match_tokens(rest, &text[i..])),
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ScanOptions {
+    // a file is valid if any of them accepts it, tried in order
+    pub encodings: Vec<Encoding>,
+    pub strategy: Strategy,
+    // files must match one of `include`, if any, and none of `exclude`;
+    // excluded directories are not entered
+    pub include: Vec<Glob>,
+    pub exclude: Vec<Glob>,
+    // symlinks met under the roots are skipped otherwise
+    pub follow_symlinks: bool,
+    // zero uses the available parallelism
+    pub threads: usize,
+}
+
+impl ScanOptions {
+    pub fn new(encodings: Vec<Encoding>) -> Self {
+        ScanOptions {
+            encodings,
+            strategy: Strategy::Simd,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            follow_symlinks: false,
+            threads: 0,
+        }
+    }
+
+    fn excluded(&self, relative: &str) -> bool {
+        self.exclude.iter().any(|glob| glob.matches(relative))
+    }
+
+    fn included(&self, relative: &str) -> bool {
+        self.include.is_empty() || self.include.iter().any(|glob| glob.matches(relative))
+    }
+}
+
+#[derive(Debug)]
+pub enum Verdict {
+    // the first of the encodings that accepts the file
+    Valid(Encoding),
+    // one error per encoding, in order
+    Invalid(Vec<FileError>),
+    // the file or directory could not be read
+    Error(io::Error),
+}
+
+#[derive(Debug)]
+pub struct FileReport {
+    pub path: PathBuf,
+    pub bytes: u64,
+    pub verdict: Verdict,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Summary {
+    // valid files per encoding, in the order of `ScanOptions::encodings`
+    pub valid: Vec<usize>,
+    pub invalid: usize,
+    pub errors: usize,
+    // symlinks not followed, and pipes, sockets and devices
+    pub skipped: usize,
+    pub bytes: u64,
+    pub elapsed: Duration,
+}
+
+impl Summary {
+    pub fn files(&self) -> usize {
+        self.valid.iter().sum::<usize>() + self.invalid + self.errors
+    }
+
+    // In bytes per second.
+    pub fn throughput(&self) -> f64 {
+        self.bytes as f64 / self.elapsed.as_secs_f64().max(1e-9)
+    }
+}
+
+// Regular files under the roots that pass the filters, and the reports of
+// the entries that could not be read.
+struct Walk {
+    files: Vec<PathBuf>,
+    failed: Vec<FileReport>,
+    skipped: usize,
+}
+
+fn walk(roots: &[PathBuf], options: &ScanOptions) -> Walk {
+    let mut walk = Walk {
+        files: Vec::new(),
+        failed: Vec::new(),
+        skipped: 0,
+    };
+    // directories entered, against symlink loops
+    let mut visited: HashSet<(u64, u64)> = HashSet::new();
+    for root in roots {
+        // roots are followed, as they were named explicitly
+        match fs::metadata(root) {
+            Ok(metadata) if metadata.is_file() => walk.files.push(root.clone()),
+            Ok(metadata) if metadata.is_dir() => {
+                let mut stack = vec![root.clone()];
+                while let Some(dir) = stack.pop() {
+                    walk.enter(&dir, root, options, &mut visited, &mut stack);
+                }
+            }
+            Ok(_) => walk.skipped += 1,
+            Err(error) => walk.fail(root.clone(), error),
+        }
+    }
+    walk
+}
+
+impl Walk {
+    fn fail(&mut self, path: PathBuf, error: io::Error) {
+        self.failed.push(FileReport {
+            path,
+            bytes: 0,
+            verdict: Verdict::Error(error),
+        });
+    }
+
+    fn enter(
+        &mut self,
+        dir: &Path,
+        root: &Path,
+        options: &ScanOptions,
+        visited: &mut HashSet<(u64, u64)>,
+        stack: &mut Vec<PathBuf>,
+    ) {
+        match fs::metadata(dir) {
+            Ok(metadata) if !visited.insert((metadata.dev(), metadata.ino())) => return,
+            Ok(_) => {}
+            Err(error) => return self.fail(dir.to_path_buf(), error),
+        }
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(error) => return self.fail(dir.to_path_buf(), error),
+        };
+        for entry in entries {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(error) => {
+                    self.fail(dir.to_path_buf(), error);
+                    continue;
+                }
+            };
+            let path = entry.path();
+            let relative = path.strip_prefix(root).unwrap_or(&path).to_string_lossy();
+            if options.excluded(&relative) {
+                continue;
+            }
+            let file_type = match entry.file_type() {
+                Ok(file_type) if file_type.is_symlink() && options.follow_symlinks => {
+                    match fs::metadata(&path) {
+                        Ok(metadata) => metadata.file_type(),
+                        Err(error) => {
+                            self.fail(path, error);
+                            continue;
+                        }
+                    }
+                }
+                Ok(file_type) => file_type,
+                Err(error) => {
+                    self.fail(path, error);
+                    continue;
+                }
+            };
+            if file_type.is_dir() {
+                stack.push(path);
+            } else if !file_type.is_file() {
+                self.skipped += 1;
+            } else if options.included(&relative) {
+                self.files.push(path);
+            }
+        }
+    }
+}
+
+fn check(path: &Path, options: &ScanOptions) -> FileReport {
+    let report = |bytes, verdict| FileReport {
+        path: path.to_path_buf(),
+        bytes,
+        verdict,
+    };
+    let contents = match Contents::load(path) {
+        Ok(contents) => contents,
+        Err(error) => return report(0, Verdict::Error(error)),
+    };
+    let data = contents.as_slice();
+    let mut errors = Vec::new();
+    for &encoding in &options.encodings {
+        match file::validate_with(data, encoding, options.strategy) {
+            Ok(()) => return report(data.len() as u64, Verdict::Valid(encoding)),
+            Err(error) => errors.push(error),
+        }
+    }
+    report(data.len() as u64, Verdict::Invalid(errors))
+}
+
+// Validates the regular files under `roots` on `options.threads` threads.
+// `report` sees every file, and every entry that could not be read, from
+// the worker threads in no particular order.
+pub fn scan<F>(roots: &[PathBuf], options: &ScanOptions, report: F) -> Summary
+where
+    F: Fn(&FileReport) + Sync,
+{
+    let start = Instant::now();
+    let walk = walk(roots, options);
+    let threads = if options.threads == 0 {
+        thread::available_parallelism().map_or(1, |n| n.get())
+    } else {
+        options.threads
+    };
+
+    let summary = Mutex::new(Summary {
+        valid: vec![0; options.encodings.len()],
+        skipped: walk.skipped,
+        ..Summary::default()
+    });
+    let record = |file: &FileReport| {
+        report(file);
+        let mut summary = summary.lock().unwrap();
+        summary.bytes += file.bytes;
+        match &file.verdict {
+            Verdict::Valid(encoding) => {
+                let index = options
+                    .encodings
+                    .iter()
+                    .position(|e| e == encoding)
+                    .unwrap();
+                summary.valid[index] += 1;
+            }
+            Verdict::Invalid(_) => summary.invalid += 1,
+            Verdict::Error(_) => summary.errors += 1,
+        }
+    };
+    walk.failed.iter().for_each(record);
+
+    let next = AtomicUsize::new(0);
+    thread::scope(|scope| {
+        for _ in 0..threads.min(walk.files.len()) {
+            scope.spawn(|| {
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(path) = walk.files.get(index) else {
+                        break;
+                    };
+                    record(&check(path, options));
+                }
+            });
+        }
+    });
+
+    let mut summary = summary.into_inner().unwrap();
+    summary.elapsed = start.elapsed();
+    summary
+}
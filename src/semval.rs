@@ -0,0 +1,118 @@
+use crate::detect;
+use crate::encoding::Encoding;
+
+// The semantic level (SemVal-S): among the encodings that accept the bytes
+// structurally, prefer the ones whose decoded text is plausible.
+
+pub trait SemanticScorer {
+    // Plausibility of `data` as text in `encoding`, higher is better.
+    // `data` is structurally valid in `encoding`.
+    fn score(&self, encoding: Encoding, data: &[u8]) -> f64;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Script {
+    Ascii,
+    Han,
+    Hangul,
+    Kana,
+    // CJK and general punctuation, full-width forms, symbols
+    Symbol,
+    // Latin beyond ASCII, Greek, Cyrillic
+    Letter,
+    Other,
+}
+
+pub fn script(c: char) -> Script {
+    match c as u32 {
+        0x00..=0x7F => Script::Ascii,
+        0x80..=0x10FF | 0x1200..=0x1FFF => Script::Letter,
+        0x1100..=0x11FF | 0x3130..=0x318F | 0xAC00..=0xD7A3 => Script::Hangul,
+        0x3040..=0x30FF | 0x31F0..=0x31FF | 0xFF66..=0xFF9F => Script::Kana,
+        0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xF900..=0xFAFF | 0x20000..=0x3134F => Script::Han,
+        0x2000..=0x2BFF | 0x3000..=0x303F | 0xFF00..=0xFF65 | 0xFFE0..=0xFFEF => Script::Symbol,
+        _ => Script::Other,
+    }
+}
+
+// Scores by the scripts the text decodes to, weighted by how typical each
+// script is for the encoding. It needs no training data, but cannot tell
+// apart two decodings that land in the same script, such as Hangul rows
+// read as GB2312 level 1 hanzi.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ScriptScorer;
+
+impl ScriptScorer {
+    fn weight(encoding: Encoding, script: Script) -> f64 {
+        match (encoding, script) {
+            (_, Script::Ascii) => 1.0,
+            (_, Script::Other) => 0.0,
+            (Encoding::Gb2312 | Encoding::Gb18030, Script::Han) => 1.0,
+            (Encoding::Gb2312 | Encoding::Gb18030, Script::Hangul) => 0.05,
+            (Encoding::EucKr, Script::Hangul) => 1.0,
+            (Encoding::EucKr, Script::Han) => 0.4,
+            (Encoding::EucJp, Script::Kana) => 1.0,
+            (Encoding::EucJp, Script::Han) => 0.8,
+            (Encoding::EucJp, Script::Hangul) => 0.0,
+            // multi-byte UTF-8 rarely validates by accident
+            (Encoding::Utf8, _) => 1.0,
+            // almost any byte pair is a UTF-16 code unit
+            (Encoding::Utf16Le | Encoding::Utf16Be, Script::Symbol) => 0.3,
+            (Encoding::Utf16Le | Encoding::Utf16Be, _) => 0.5,
+            (_, Script::Symbol) => 0.5,
+            (_, _) => 0.2,
+        }
+    }
+}
+
+impl SemanticScorer for ScriptScorer {
+    // Mean weight of the non-ASCII characters; ASCII-only text scores 1.0
+    // in every encoding.
+    fn score(&self, encoding: Encoding, data: &[u8]) -> f64 {
+        let (text, _) = encoding.rs_encoding().decode_without_bom_handling(data);
+        let mut total = 0.0;
+        let mut count = 0;
+        for c in text.chars().filter(|c| !c.is_ascii()) {
+            total += ScriptScorer::weight(encoding, script(c));
+            count += 1;
+        }
+        if count == 0 {
+            1.0
+        } else {
+            total / count as f64
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Scored {
+    pub encoding: Encoding,
+    pub score: f64,
+}
+
+// Both levels in one call: the structurally valid encodings, best score
+// first. Ties keep the order of `Encoding::ALL`.
+pub fn rank<S: SemanticScorer + ?Sized>(data: &[u8], scorer: &S) -> Vec<Scored> {
+    rank_among(data, &Encoding::ALL, scorer)
+}
+
+pub fn rank_among<S: SemanticScorer + ?Sized>(
+    data: &[u8],
+    encodings: &[Encoding],
+    scorer: &S,
+) -> Vec<Scored> {
+    let mut scored: Vec<Scored> = detect::detect_among(data, encodings)
+        .into_iter()
+        .filter(detect::Candidate::is_valid)
+        .map(|candidate| Scored {
+            encoding: candidate.encoding,
+            score: scorer.score(candidate.encoding, data),
+        })
+        .collect();
+    scored.sort_by(|a, b| b.score.total_cmp(&a.score));
+    scored
+}
+
+pub fn best<S: SemanticScorer + ?Sized>(data: &[u8], scorer: &S) -> Option<Encoding> {
+    rank(data, scorer).first().map(|scored| scored.encoding)
+}
@@ -0,0 +1,186 @@
+use crate::encoding::Encoding;
+use crate::model::{self, FrequencyModel};
+use crate::multi;
+use std::sync::OnceLock;
+
+// Disambiguation of short inputs, roughly 2 to 64 bytes, between GB2312,
+// EUC-KR and EUC-JP. Short texts are often valid in all three because the
+// tables overlap in rows 0xB0-0xC8, so the structural answer alone does not
+// decide.
+
+// weight of the character counts against the row prior
+const LAMBDA: f64 = 0.9;
+const ALPHA: f64 = 0.5;
+// characters per row
+const ROW_SIZE: f64 = 94.0;
+
+pub const DEFAULT_MIN_CONFIDENCE: f64 = 0.75;
+
+// A frequency model together with how its characters spread over the lead
+// byte rows of its encoding. The row prior carries what a novel-sized
+// corpus cannot: Hangul in rows 0xB0-0xC8 of KS X 1001, level 1 hanzi in
+// rows 0xB0-0xD7 of GB2312, kana in rows 0xA4/0xA5 of JIS X 0208.
+#[derive(Clone, Debug)]
+pub struct RowModel {
+    model: FrequencyModel,
+    rows: [u64; 128],
+}
+
+impl RowModel {
+    pub fn new(model: FrequencyModel) -> Self {
+        let mut rows = [0; 128];
+        let encoder = model.encoding().rs_encoding();
+        let mut buffer = [0; 4];
+        for (c, count) in model.chars() {
+            let (bytes, _, had_errors) = encoder.encode(c.encode_utf8(&mut buffer));
+            if !had_errors && bytes[0] >= 0x80 {
+                rows[(bytes[0] - 0x80) as usize] += count;
+            }
+        }
+        RowModel { model, rows }
+    }
+
+    pub fn encoding(&self) -> Encoding {
+        self.model.encoding()
+    }
+
+    pub fn model(&self) -> &FrequencyModel {
+        &self.model
+    }
+
+    pub fn row_probability(&self, lead: u8) -> f64 {
+        let row = if lead >= 0x80 {
+            self.rows[(lead - 0x80) as usize]
+        } else {
+            0
+        };
+        (row as f64 + ALPHA) / (self.model.total() as f64 + ALPHA * 128.0)
+    }
+
+    // Interpolates the character frequency with a uniform share of its row.
+    fn log_probability(&self, lead: u8, c: char) -> f64 {
+        let total = self.model.total().max(1) as f64;
+        let char_probability = self.model.count(c) as f64 / total;
+        (LAMBDA * char_probability + (1.0 - LAMBDA) * self.row_probability(lead) / ROW_SIZE).ln()
+    }
+
+    // Log-likelihood of the non-ASCII characters of `data`, which is
+    // structurally valid in the model's encoding.
+    pub fn log_likelihood(&self, data: &[u8]) -> f64 {
+        let encoding = self.encoding();
+        let decoder = encoding.rs_encoding();
+        let mut sum = 0.0;
+        let mut i = 0;
+        while i < data.len() {
+            let len = encoding.char_len(data, i).unwrap_or(1);
+            if data[i] >= 0x80 {
+                let (text, _) = decoder.decode_without_bom_handling(&data[i..i + len]);
+                if let Some(c) = text.chars().next() {
+                    sum += self.log_probability(data[i], c);
+                }
+            }
+            i += len;
+        }
+        sum
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ranked {
+    pub encoding: Encoding,
+    // posterior probability among the structurally valid candidates
+    pub confidence: f64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Verdict {
+    // best first; the confidences add up to 1
+    Ranked(Vec<Ranked>),
+    // the best candidate falls below the minimum confidence, or the input
+    // has no non-ASCII byte to tell the candidates apart
+    Undecidable(Vec<Ranked>),
+    // not valid in any of the encodings
+    Invalid,
+}
+
+impl Verdict {
+    pub fn best(&self) -> Option<Encoding> {
+        match self {
+            Verdict::Ranked(ranking) => ranking.first().map(|ranked| ranked.encoding),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Disambiguator {
+    models: Vec<RowModel>,
+    min_confidence: f64,
+}
+
+impl Disambiguator {
+    pub fn new(models: Vec<FrequencyModel>, min_confidence: f64) -> Self {
+        Disambiguator {
+            models: models.into_iter().map(RowModel::new).collect(),
+            min_confidence,
+        }
+    }
+
+    pub fn models(&self) -> &[RowModel] {
+        &self.models
+    }
+
+    pub fn min_confidence(&self) -> f64 {
+        self.min_confidence
+    }
+
+    pub fn disambiguate(&self, data: &[u8]) -> Verdict {
+        let valid = multi::multi_valid_encodings(data);
+        let candidates: Vec<&RowModel> = self
+            .models
+            .iter()
+            .filter(|model| valid.contains(&model.encoding()))
+            .collect();
+        if candidates.is_empty() {
+            return Verdict::Invalid;
+        }
+
+        // softmax over the log-likelihoods, with equal priors per encoding
+        let scores: Vec<f64> = candidates
+            .iter()
+            .map(|model| model.log_likelihood(data))
+            .collect();
+        let max = scores.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let weights: Vec<f64> = scores.iter().map(|score| (score - max).exp()).collect();
+        let sum: f64 = weights.iter().sum();
+        let mut ranking: Vec<Ranked> = candidates
+            .iter()
+            .zip(weights)
+            .map(|(model, weight)| Ranked {
+                encoding: model.encoding(),
+                confidence: weight / sum,
+            })
+            .collect();
+        ranking.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+
+        if data.is_ascii() || ranking[0].confidence < self.min_confidence {
+            Verdict::Undecidable(ranking)
+        } else {
+            Verdict::Ranked(ranking)
+        }
+    }
+}
+
+impl Default for Disambiguator {
+    fn default() -> Self {
+        Disambiguator::new(model::default_models().to_vec(), DEFAULT_MIN_CONFIDENCE)
+    }
+}
+
+static DEFAULT_DISAMBIGUATOR: OnceLock<Disambiguator> = OnceLock::new();
+
+pub fn disambiguate(data: &[u8]) -> Verdict {
+    DEFAULT_DISAMBIGUATOR
+        .get_or_init(Disambiguator::default)
+        .disambiguate(data)
+}
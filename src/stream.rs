@@ -0,0 +1,119 @@
+use crate::encoding::Encoding;
+use crate::error::{ErrorKind, ValidationError};
+
+// Longest character in any supported encoding: GB18030 four-byte
+// sequences, UTF-8 four-byte sequences and UTF-16 surrogate pairs.
+const MAX_CHAR_LEN: usize = 4;
+
+// Validates input that arrives in chunks. Each chunk goes through the SIMD
+// kernel; a character split across chunks is held back until the next
+// `feed` completes it, like the GB18030 `State` machine does per byte.
+// After an error every later call reports the same error.
+#[derive(Clone, Debug)]
+pub struct StreamValidator {
+    encoding: Encoding,
+    // absolute offset of the first byte not yet validated
+    position: usize,
+    pending: [u8; MAX_CHAR_LEN],
+    pending_len: usize,
+    error: Option<ValidationError>,
+}
+
+impl StreamValidator {
+    pub fn new(encoding: Encoding) -> Self {
+        StreamValidator {
+            encoding,
+            position: 0,
+            pending: [0; MAX_CHAR_LEN],
+            pending_len: 0,
+            error: None,
+        }
+    }
+
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    // Number of bytes that form complete, valid characters so far; after an
+    // error, the offset of the error.
+    pub fn valid_up_to(&self) -> usize {
+        self.position
+    }
+
+    pub fn error(&self) -> Option<ValidationError> {
+        self.error
+    }
+
+    // The start of a character still waiting for its remaining bytes.
+    pub fn pending(&self) -> &[u8] {
+        &self.pending[..self.pending_len]
+    }
+
+    fn fail(&mut self, offset: usize, kind: ErrorKind) -> Result<(), ValidationError> {
+        let error = ValidationError {
+            encoding: self.encoding,
+            offset,
+            kind,
+        };
+        self.error = Some(error);
+        Err(error)
+    }
+
+    pub fn feed(&mut self, chunk: &[u8]) -> Result<(), ValidationError> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        let mut chunk = chunk;
+
+        if self.pending_len > 0 {
+            // complete the held-back character with the head of the chunk
+            let take = chunk.len().min(MAX_CHAR_LEN - self.pending_len);
+            let mut buffer = self.pending;
+            buffer[self.pending_len..self.pending_len + take].copy_from_slice(&chunk[..take]);
+            let available = self.pending_len + take;
+            match self.encoding.char_len(&buffer[..available], 0) {
+                Ok(len) => {
+                    chunk = &chunk[len - self.pending_len..];
+                    self.position += len;
+                    self.pending_len = 0;
+                }
+                Err(ErrorKind::Truncated) => {
+                    self.pending = buffer;
+                    self.pending_len = available;
+                    return Ok(());
+                }
+                Err(kind) => return self.fail(self.position, kind),
+            }
+        }
+
+        let mut i = self.encoding.valid_up_to(chunk);
+        while i < chunk.len() {
+            match self.encoding.char_len(chunk, i) {
+                Ok(len) => i += len,
+                Err(ErrorKind::Truncated) => {
+                    let rest = &chunk[i..];
+                    self.pending[..rest.len()].copy_from_slice(rest);
+                    self.pending_len = rest.len();
+                    break;
+                }
+                Err(kind) => {
+                    self.position += i;
+                    return self.fail(self.position, kind);
+                }
+            }
+        }
+        self.position += i;
+        Ok(())
+    }
+
+    // Ends the stream; a character still pending is truncated.
+    pub fn finish(&mut self) -> Result<(), ValidationError> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        if self.pending_len > 0 {
+            return self.fail(self.position, ErrorKind::Truncated);
+        }
+        Ok(())
+    }
+}
@@ -1,5 +1,5 @@
 use libc::{c_char, c_void, size_t};
-use std::arch::x86_64::{__m128i, __m256i};
+use std::arch::x86_64::{__m128i, __m256i, _mm_loadu_si128, _mm_movemask_epi8};
 use std::ffi::CString;
 
 use encoding_rs::Encoding;
@@ -71,6 +71,12 @@ pub fn build_table(valid_ranges: &[&[(u8, u8)]; 128]) -> [bool; 32768] {
     table
 }
 
+pub fn in_ranges(ranges: &[(u8, u8)], byte: u8) -> bool {
+    ranges
+        .iter()
+        .any(|&(start, end)| (start..=end).contains(&byte))
+}
+
 pub fn build_i32_table_from_bool(table: &[bool; 32768]) -> [i32; 32768] {
     let mut i32_table = [0; 32768];
     for i in 0..32768 {
@@ -79,6 +85,149 @@ pub fn build_i32_table_from_bool(table: &[bool; 32768]) -> [i32; 32768] {
     i32_table
 }
 
+// Unicode scalar of each pair marked valid in `table`, as `decoder` decodes
+// it; zero for the other pairs. Every valid pair maps into the BMP.
+pub fn build_unicode_table(table: &[bool; 32768], decoder: &'static Encoding) -> [u16; 32768] {
+    let mut unicode = [0; 32768];
+    for (index, &valid) in table.iter().enumerate() {
+        if valid {
+            let pair = [(index / 256) as u8 + 0x80, index as u8];
+            unicode[index] = decode_bmp(decoder, &pair);
+        }
+    }
+    unicode
+}
+
+// The single BMP character `bytes` decodes to, or zero. That may be U+FFFD:
+// the validators accept a few pairs that encoding_rs does not map.
+pub fn decode_bmp(decoder: &'static Encoding, bytes: &[u8]) -> u16 {
+    let (text, _) = decoder.decode_without_bom_handling(bytes);
+    let mut chars = text.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if (c as u32) < 0x10000 => c as u16,
+        _ => 0,
+    }
+}
+
+// The UTF-8 bytes of each entry of a Unicode table, little-endian, with
+// their count in the top byte; zero entries stay zero. The fused decoders
+// write a whole entry at once.
+pub fn build_utf8_table(unicode: &[u16; 32768]) -> Utf8Table {
+    let mut utf8 = Box::new([0; 32768]);
+    for (entry, &code_point) in utf8.iter_mut().zip(unicode.iter()) {
+        if let Some(c) = char::from_u32(code_point as u32).filter(|_| code_point != 0) {
+            let mut bytes = [0; 4];
+            let len = c.encode_utf8(&mut bytes).len();
+            *entry = u32::from_le_bytes(bytes) | (len as u32) << 24;
+        }
+    }
+    Utf8Table(utf8)
+}
+
+// A table of `build_utf8_table`, the only way to make one: every nonzero
+// entry holds one whole character of one to three bytes, which the fused
+// decoders copy out unchecked.
+pub struct Utf8Table(Box<[u32; 32768]>);
+
+impl std::ops::Deref for Utf8Table {
+    type Target = [u32; 32768];
+
+    fn deref(&self) -> &[u32; 32768] {
+        &self.0
+    }
+}
+
+// Length of the ASCII run at the start of `data`, sixteen bytes at a time
+// while whole blocks are ASCII.
+pub fn ascii_prefix(data: &[u8]) -> usize {
+    let mut i = 0;
+    while i + 16 <= data.len() {
+        let mask =
+            unsafe { _mm_movemask_epi8(_mm_loadu_si128(data[i..].as_ptr() as *const __m128i)) };
+        if mask != 0 {
+            return i + mask.trailing_zeros() as usize;
+        }
+        i += 16;
+    }
+    while i < data.len() && data[i] < 0x80 {
+        i += 1;
+    }
+    i
+}
+
+// Output of the fused decoders. No input character takes more than one and
+// a half times its length in UTF-8, so the buffer is sized for that up
+// front and the push methods write without capacity checks. Callers keep
+// to that bound: each push needs its input bytes to have been counted in
+// `for_input` and not yet used by an earlier push.
+pub(crate) struct Utf8Output {
+    bytes: Vec<u8>,
+    len: usize,
+}
+
+impl Utf8Output {
+    pub(crate) fn for_input(len: usize) -> Self {
+        // room for the whole word written by `push_entry`
+        Utf8Output {
+            bytes: Vec::with_capacity(len + len / 2 + 4),
+            len: 0,
+        }
+    }
+
+    // Copies the ASCII bytes at the start of `data` and returns how many
+    // were copied.
+    //
+    // Safety: `data` is input not yet pushed.
+    pub(crate) unsafe fn push_ascii(&mut self, data: &[u8]) -> usize {
+        let len = ascii_prefix(data);
+        debug_assert!(self.len + len <= self.bytes.capacity());
+        unsafe {
+            std::ptr::copy_nonoverlapping(data.as_ptr(), self.bytes.as_mut_ptr().add(self.len), len)
+        };
+        self.len += len;
+        len
+    }
+
+    // An entry of a `Utf8Table`.
+    //
+    // Safety: the entry is nonzero and stands for at least two input bytes
+    // not yet pushed.
+    pub(crate) unsafe fn push_entry(&mut self, entry: u32) {
+        debug_assert!(entry != 0 && self.len + 4 <= self.bytes.capacity());
+        unsafe {
+            (self.bytes.as_mut_ptr().add(self.len) as *mut u32).write_unaligned(entry);
+        }
+        self.len += (entry >> 24) as usize;
+    }
+
+    // A character that is not in the tables.
+    //
+    // Safety: the character stands for at least two thirds as many input
+    // bytes not yet pushed as it takes in UTF-8.
+    pub(crate) unsafe fn push_char(&mut self, c: char) {
+        let mut bytes = [0; 4];
+        let len = c.encode_utf8(&mut bytes).len();
+        debug_assert!(self.len + len <= self.bytes.capacity());
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                bytes.as_ptr(),
+                self.bytes.as_mut_ptr().add(self.len),
+                len,
+            )
+        };
+        self.len += len;
+    }
+
+    pub(crate) fn into_string(mut self) -> String {
+        // only whole characters were written: ASCII, `Utf8Table` entries
+        // and chars
+        unsafe {
+            self.bytes.set_len(self.len);
+            String::from_utf8_unchecked(self.bytes)
+        }
+    }
+}
+
 pub static LEAD_BYTE_MASK: __m128i =
     unsafe { std::mem::transmute([0i8, 2, 4, 6, 8, 10, 12, 14, -1, -1, -1, -1, -1, -1, -1, -1]) };
 
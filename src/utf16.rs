@@ -0,0 +1,151 @@
+use crate::error::ErrorKind;
+use crate::tools;
+use encoding_rs::{UTF_16BE, UTF_16LE};
+use std::arch::x86_64::*;
+pub fn is_utf16le_iconv(data: &[u8]) -> bool {
+    tools::is_encoding_iconv(data, "UTF-16LE")
+}
+
+pub fn is_utf16be_iconv(data: &[u8]) -> bool {
+    tools::is_encoding_iconv(data, "UTF-16BE")
+}
+
+pub fn is_utf16le_rs(data: &[u8]) -> bool {
+    tools::is_encoding_rs(data, UTF_16LE)
+}
+
+pub fn is_utf16be_rs(data: &[u8]) -> bool {
+    tools::is_encoding_rs(data, UTF_16BE)
+}
+
+#[inline]
+fn unit(data: &[u8], i: usize, big_endian: bool) -> u16 {
+    if big_endian {
+        u16::from_be_bytes([data[i], data[i + 1]])
+    } else {
+        u16::from_le_bytes([data[i], data[i + 1]])
+    }
+}
+
+// Validates the code units starting in `data[start..end]`; a surrogate pair
+// may run past `end`. Returns the offset after the last code unit.
+fn utf16_fallback(data: &[u8], start: usize, end: usize, big_endian: bool) -> Option<usize> {
+    let mut i = start;
+    while i < end {
+        if i + 1 >= data.len() {
+            return None;
+        }
+        match unit(data, i, big_endian) {
+            // high surrogate, must be followed by a low surrogate
+            0xD800..=0xDBFF => {
+                if i + 3 >= data.len() {
+                    return None;
+                }
+                if !(0xDC00..=0xDFFF).contains(&unit(data, i + 2, big_endian)) {
+                    return None;
+                }
+                i += 4;
+            }
+            // unpaired low surrogate
+            0xDC00..=0xDFFF => return None,
+            _ => i += 2,
+        }
+    }
+    Some(i)
+}
+
+fn is_utf16_range(data: &[u8], big_endian: bool) -> bool {
+    utf16_fallback(data, 0, data.len(), big_endian).is_some()
+}
+
+pub fn is_utf16le_range(data: &[u8]) -> bool {
+    is_utf16_range(data, false)
+}
+
+pub fn is_utf16be_range(data: &[u8]) -> bool {
+    is_utf16_range(data, true)
+}
+
+static F8: __m128i = unsafe { std::mem::transmute([0xF8u8 as i8; 16]) };
+static D8: __m128i = unsafe { std::mem::transmute([0xD8u8 as i8; 16]) };
+
+// movemask bits of the high byte of each code unit
+const HIGH_BYTES_LE: i32 = 0xAAAA;
+const HIGH_BYTES_BE: i32 = 0x5555;
+
+// Length of the prefix accepted by the SIMD kernel; always a character
+// boundary. Stops at the first block containing an error or at the tail.
+fn utf16_valid_up_to(data: &[u8], big_endian: bool) -> usize {
+    let high_bytes = if big_endian {
+        HIGH_BYTES_BE
+    } else {
+        HIGH_BYTES_LE
+    };
+    let len = data.len();
+    let mut i = 0;
+    while i + 15 < len {
+        let surrogates = unsafe {
+            let chunk = _mm_loadu_si128(data[i..].as_ptr() as *const __m128i);
+            let cmp = _mm_cmpeq_epi8(_mm_and_si128(chunk, F8), D8);
+            _mm_movemask_epi8(cmp) & high_bytes
+        };
+        if surrogates == 0 {
+            // eight BMP code units
+            i += 16;
+            continue;
+        }
+        match utf16_fallback(data, i, i + 16, big_endian) {
+            Some(next) => i = next,
+            None => return i,
+        }
+    }
+    i
+}
+
+fn is_utf16_simd(data: &[u8], big_endian: bool) -> bool {
+    let i = utf16_valid_up_to(data, big_endian);
+    i >= data.len() || utf16_fallback(data, i, data.len(), big_endian).is_some()
+}
+
+pub fn is_utf16le_simd(data: &[u8]) -> bool {
+    is_utf16_simd(data, false)
+}
+
+pub fn is_utf16be_simd(data: &[u8]) -> bool {
+    is_utf16_simd(data, true)
+}
+
+pub fn utf16le_valid_up_to(data: &[u8]) -> usize {
+    utf16_valid_up_to(data, false)
+}
+
+pub fn utf16be_valid_up_to(data: &[u8]) -> usize {
+    utf16_valid_up_to(data, true)
+}
+
+fn utf16_char_len(data: &[u8], i: usize, big_endian: bool) -> Result<usize, ErrorKind> {
+    if i + 1 >= data.len() {
+        return Err(ErrorKind::Truncated);
+    }
+    match unit(data, i, big_endian) {
+        0xD800..=0xDBFF => {
+            if i + 3 >= data.len() {
+                return Err(ErrorKind::Truncated);
+            }
+            if !(0xDC00..=0xDFFF).contains(&unit(data, i + 2, big_endian)) {
+                return Err(ErrorKind::Surrogate);
+            }
+            Ok(4)
+        }
+        0xDC00..=0xDFFF => Err(ErrorKind::Surrogate),
+        _ => Ok(2),
+    }
+}
+
+pub fn utf16le_char_len(data: &[u8], i: usize) -> Result<usize, ErrorKind> {
+    utf16_char_len(data, i, false)
+}
+
+pub fn utf16be_char_len(data: &[u8], i: usize) -> Result<usize, ErrorKind> {
+    utf16_char_len(data, i, true)
+}
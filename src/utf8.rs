@@ -0,0 +1,267 @@
+use crate::error::ErrorKind;
+use crate::tools;
+use encoding_rs::UTF_8;
+use std::arch::x86_64::*;
+pub fn is_utf8_iconv(data: &[u8]) -> bool {
+    tools::is_encoding_iconv(data, "UTF-8")
+}
+
+pub fn is_utf8_rs(data: &[u8]) -> bool {
+    tools::is_encoding_rs(data, UTF_8)
+}
+
+pub fn is_utf8_range(data: &[u8]) -> bool {
+    let mut i = 0;
+    while i < data.len() {
+        let lead = data[i];
+        // valid range of the second byte, and the sequence length
+        let (second, len) = match lead {
+            0x00..=0x7F => {
+                i += 1;
+                continue;
+            }
+            0xC2..=0xDF => ((0x80, 0xBF), 2),
+            0xE0 => ((0xA0, 0xBF), 3),
+            0xE1..=0xEC | 0xEE..=0xEF => ((0x80, 0xBF), 3),
+            0xED => ((0x80, 0x9F), 3),
+            0xF0 => ((0x90, 0xBF), 4),
+            0xF1..=0xF3 => ((0x80, 0xBF), 4),
+            0xF4 => ((0x80, 0x8F), 4),
+            _ => return false,
+        };
+        if i + len > data.len() {
+            return false;
+        }
+        if !(second.0..=second.1).contains(&data[i + 1]) {
+            return false;
+        }
+        for j in 2..len {
+            if !(0x80..=0xBF).contains(&data[i + j]) {
+                return false;
+            }
+        }
+        i += len;
+    }
+    true
+}
+
+// Error bits of the lookup tables, after simdjson
+// (Keiser & Lemire, "Validating UTF-8 In Less Than One Instruction Per Byte").
+const TOO_SHORT: u8 = 1 << 0;
+const TOO_LONG: u8 = 1 << 1;
+const OVERLONG_3: u8 = 1 << 2;
+const TOO_LARGE: u8 = 1 << 3;
+const SURROGATE: u8 = 1 << 4;
+const OVERLONG_2: u8 = 1 << 5;
+const TOO_LARGE_1000: u8 = 1 << 6;
+const OVERLONG_4: u8 = 1 << 6;
+const TWO_CONTS: u8 = 1 << 7;
+const CARRY: u8 = TOO_SHORT | TOO_LONG | TWO_CONTS;
+
+// indexed by the high nibble of the previous byte
+static BYTE_1_HIGH: __m128i = unsafe {
+    std::mem::transmute([
+        TOO_LONG,
+        TOO_LONG,
+        TOO_LONG,
+        TOO_LONG,
+        TOO_LONG,
+        TOO_LONG,
+        TOO_LONG,
+        TOO_LONG,
+        TWO_CONTS,
+        TWO_CONTS,
+        TWO_CONTS,
+        TWO_CONTS,
+        TOO_SHORT | OVERLONG_2,
+        TOO_SHORT,
+        TOO_SHORT | OVERLONG_3 | SURROGATE,
+        TOO_SHORT | TOO_LARGE | TOO_LARGE_1000 | OVERLONG_4,
+    ])
+};
+
+// indexed by the low nibble of the previous byte
+static BYTE_1_LOW: __m128i = unsafe {
+    std::mem::transmute([
+        CARRY | OVERLONG_3 | OVERLONG_2 | OVERLONG_4,
+        CARRY | OVERLONG_2,
+        CARRY,
+        CARRY,
+        CARRY | TOO_LARGE,
+        CARRY | TOO_LARGE | TOO_LARGE_1000,
+        CARRY | TOO_LARGE | TOO_LARGE_1000,
+        CARRY | TOO_LARGE | TOO_LARGE_1000,
+        CARRY | TOO_LARGE | TOO_LARGE_1000,
+        CARRY | TOO_LARGE | TOO_LARGE_1000,
+        CARRY | TOO_LARGE | TOO_LARGE_1000,
+        CARRY | TOO_LARGE | TOO_LARGE_1000,
+        CARRY | TOO_LARGE | TOO_LARGE_1000,
+        CARRY | TOO_LARGE | TOO_LARGE_1000 | SURROGATE,
+        CARRY | TOO_LARGE | TOO_LARGE_1000,
+        CARRY | TOO_LARGE | TOO_LARGE_1000,
+    ])
+};
+
+// indexed by the high nibble of the current byte
+static BYTE_2_HIGH: __m128i = unsafe {
+    std::mem::transmute([
+        TOO_SHORT,
+        TOO_SHORT,
+        TOO_SHORT,
+        TOO_SHORT,
+        TOO_SHORT,
+        TOO_SHORT,
+        TOO_SHORT,
+        TOO_SHORT,
+        TOO_LONG | OVERLONG_2 | TWO_CONTS | OVERLONG_3 | TOO_LARGE_1000 | OVERLONG_4,
+        TOO_LONG | OVERLONG_2 | TWO_CONTS | OVERLONG_3 | TOO_LARGE,
+        TOO_LONG | OVERLONG_2 | TWO_CONTS | SURROGATE | TOO_LARGE,
+        TOO_LONG | OVERLONG_2 | TWO_CONTS | SURROGATE | TOO_LARGE,
+        TOO_SHORT,
+        TOO_SHORT,
+        TOO_SHORT,
+        TOO_SHORT,
+    ])
+};
+
+// a block is incomplete if it ends inside a multi-byte sequence
+static INCOMPLETE_MAX: __m128i = unsafe {
+    std::mem::transmute([
+        0xFFu8, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xEF, 0xDF,
+        0xBF,
+    ])
+};
+
+static NIBBLE: __m128i = unsafe { std::mem::transmute([0x0Fu8 as i8; 16]) };
+static THIRD_BYTE: __m128i = unsafe { std::mem::transmute([(0xE0u8 - 0x80) as i8; 16]) };
+static FOURTH_BYTE: __m128i = unsafe { std::mem::transmute([(0xF0u8 - 0x80) as i8; 16]) };
+
+#[inline]
+fn check_block(input: __m128i, prev_input: __m128i) -> __m128i {
+    unsafe {
+        let prev1 = _mm_alignr_epi8::<15>(input, prev_input);
+        let byte_1_high = _mm_shuffle_epi8(
+            BYTE_1_HIGH,
+            _mm_and_si128(_mm_srli_epi16::<4>(prev1), NIBBLE),
+        );
+        let byte_1_low = _mm_shuffle_epi8(BYTE_1_LOW, _mm_and_si128(prev1, NIBBLE));
+        let byte_2_high = _mm_shuffle_epi8(
+            BYTE_2_HIGH,
+            _mm_and_si128(_mm_srli_epi16::<4>(input), NIBBLE),
+        );
+        let special_cases = _mm_and_si128(_mm_and_si128(byte_1_high, byte_1_low), byte_2_high);
+
+        // bytes two and three positions after a 3- or 4-byte lead must be continuations
+        let prev2 = _mm_alignr_epi8::<14>(input, prev_input);
+        let prev3 = _mm_alignr_epi8::<13>(input, prev_input);
+        let is_third_byte = _mm_subs_epu8(prev2, THIRD_BYTE);
+        let is_fourth_byte = _mm_subs_epu8(prev3, FOURTH_BYTE);
+        let must_be_23 = _mm_and_si128(_mm_or_si128(is_third_byte, is_fourth_byte), tools::EIGHTY);
+        _mm_xor_si128(must_be_23, special_cases)
+    }
+}
+
+pub fn is_utf8_simd(data: &[u8]) -> bool {
+    let len = data.len();
+    let mut i = 0;
+    unsafe {
+        let mut error = _mm_setzero_si128();
+        let mut prev_input = _mm_setzero_si128();
+        let mut prev_incomplete = _mm_setzero_si128();
+        while i + 15 < len {
+            let input = _mm_loadu_si128(data[i..].as_ptr() as *const __m128i);
+            if _mm_movemask_epi8(input) == 0 {
+                // all ASCII, only an unfinished sequence before it can fail
+                error = _mm_or_si128(error, prev_incomplete);
+            } else {
+                error = _mm_or_si128(error, check_block(input, prev_input));
+                prev_incomplete = _mm_subs_epu8(input, INCOMPLETE_MAX);
+            }
+            prev_input = input;
+            i += 16;
+        }
+        if i < len {
+            // zero padding is ASCII, so a truncated tail shows up as TOO_SHORT
+            let mut tail = [0u8; 16];
+            tail[..len - i].copy_from_slice(&data[i..]);
+            let input = _mm_loadu_si128(tail.as_ptr() as *const __m128i);
+            error = _mm_or_si128(error, check_block(input, prev_input));
+        } else {
+            error = _mm_or_si128(error, prev_incomplete);
+        }
+        _mm_movemask_epi8(_mm_cmpeq_epi8(error, _mm_setzero_si128())) == 0xFFFF
+    }
+}
+
+// Start of the character containing `data[i - 1]`, or `i` at the start of
+// input. Everything before it has passed the block checks.
+fn last_boundary(data: &[u8], i: usize) -> usize {
+    if i == 0 {
+        return 0;
+    }
+    let mut j = i - 1;
+    while j > 0 && j + 4 > i && data[j] & 0xC0 == 0x80 {
+        j -= 1;
+    }
+    j
+}
+
+// Length of the prefix accepted by the SIMD kernel; always a character
+// boundary. Stops before the first block that reports an error or at the tail.
+pub fn utf8_valid_up_to(data: &[u8]) -> usize {
+    let len = data.len();
+    let mut i = 0;
+    unsafe {
+        let mut prev_input = _mm_setzero_si128();
+        let mut prev_incomplete = _mm_setzero_si128();
+        while i + 15 < len {
+            let input = _mm_loadu_si128(data[i..].as_ptr() as *const __m128i);
+            let error = if _mm_movemask_epi8(input) == 0 {
+                prev_incomplete
+            } else {
+                prev_incomplete = _mm_subs_epu8(input, INCOMPLETE_MAX);
+                check_block(input, prev_input)
+            };
+            if _mm_movemask_epi8(_mm_cmpeq_epi8(error, _mm_setzero_si128())) != 0xFFFF {
+                return last_boundary(data, i);
+            }
+            prev_input = input;
+            i += 16;
+        }
+    }
+    last_boundary(data, i)
+}
+
+pub fn utf8_char_len(data: &[u8], i: usize) -> Result<usize, ErrorKind> {
+    let lead = data[i];
+    let (second, len) = match lead {
+        0x00..=0x7F => return Ok(1),
+        0x80..=0xBF => return Err(ErrorKind::InvalidLead),
+        0xC0..=0xC1 => return Err(ErrorKind::Overlong),
+        0xC2..=0xDF => ((0x80, 0xBF), 2),
+        0xE0 => ((0xA0, 0xBF), 3),
+        0xE1..=0xEC | 0xEE..=0xEF => ((0x80, 0xBF), 3),
+        0xED => ((0x80, 0x9F), 3),
+        0xF0 => ((0x90, 0xBF), 4),
+        0xF1..=0xF3 => ((0x80, 0xBF), 4),
+        0xF4 => ((0x80, 0x8F), 4),
+        0xF5..=0xFF => return Err(ErrorKind::OutOfRange),
+    };
+    for j in 1..len {
+        if i + j >= data.len() {
+            return Err(ErrorKind::Truncated);
+        }
+        let byte = data[i + j];
+        if !(0x80..=0xBF).contains(&byte) {
+            return Err(ErrorKind::InvalidTrail);
+        }
+        if j == 1 && !(second.0..=second.1).contains(&byte) {
+            return Err(match lead {
+                0xE0 | 0xF0 => ErrorKind::Overlong,
+                0xED => ErrorKind::Surrogate,
+                _ => ErrorKind::OutOfRange,
+            });
+        }
+    }
+    Ok(len)
+}
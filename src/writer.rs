@@ -0,0 +1,142 @@
+use crate::encoding::Encoding;
+use crate::error::{ErrorKind, ValidationError};
+use crate::stream::StreamValidator;
+use std::io::{self, Write};
+
+// Validates bytes before forwarding them to `inner`. Only complete
+// characters are forwarded: a trailing partial character is held back
+// until a later write completes it. Invalid input fails the write with an
+// `io::ErrorKind::InvalidData` error, see `reader::validation_error`, after
+// the bytes before it have been forwarded.
+//
+// Once validated, bytes belong to the writer: if `inner` fails to take
+// them, they are kept and forwarded first on the next write or flush,
+// which report the error if it persists.
+//
+// Call `finish` at the end: dropping the writer discards a held-back
+// partial character, so the output stays valid but may be incomplete.
+pub struct ValidatingWriter<W: Write> {
+    inner: W,
+    validator: StreamValidator,
+    // complete characters taken from the caller so far
+    written: usize,
+    // the end of them, which `inner` failed to take
+    unforwarded: Vec<u8>,
+}
+
+impl<W: Write> ValidatingWriter<W> {
+    pub fn new(inner: W, encoding: Encoding) -> Self {
+        ValidatingWriter {
+            inner,
+            validator: StreamValidator::new(encoding),
+            written: 0,
+            unforwarded: Vec::new(),
+        }
+    }
+
+    pub fn encoding(&self) -> Encoding {
+        self.validator.encoding()
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    // The error that `flush` reports: an earlier validation error, or a
+    // partial character held back.
+    fn flush_error(&self) -> Option<ValidationError> {
+        if self.validator.error().is_some() {
+            self.validator.error()
+        } else if self.validator.pending().is_empty() {
+            None
+        } else {
+            Some(ValidationError {
+                encoding: self.validator.encoding(),
+                offset: self.validator.valid_up_to(),
+                kind: ErrorKind::Truncated,
+            })
+        }
+    }
+
+    // Fails if the output would end inside a character, otherwise flushes
+    // and returns the inner writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.validator.finish()?;
+        self.forward_unforwarded()?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+
+    // Writes to `inner` until it fails, and returns what it did not take.
+    fn forward<'a>(&mut self, mut bytes: &'a [u8]) -> (&'a [u8], io::Result<()>) {
+        while !bytes.is_empty() {
+            match self.inner.write(bytes) {
+                Ok(0) => return (bytes, Err(io::ErrorKind::WriteZero.into())),
+                Ok(n) => bytes = &bytes[n..],
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+                Err(error) => return (bytes, Err(error)),
+            }
+        }
+        (bytes, Ok(()))
+    }
+
+    fn forward_unforwarded(&mut self) -> io::Result<()> {
+        if self.unforwarded.is_empty() {
+            return Ok(());
+        }
+        let unforwarded = std::mem::take(&mut self.unforwarded);
+        let (rest, result) = self.forward(&unforwarded);
+        self.unforwarded = rest.to_vec();
+        result
+    }
+}
+
+impl<W: Write> Write for ValidatingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // nothing of `buf` is taken until `inner` has caught up
+        self.forward_unforwarded()?;
+
+        let mut held = [0; 4];
+        let held_len = self.validator.pending().len();
+        held[..held_len].copy_from_slice(self.validator.pending());
+
+        let result = self.validator.feed(buf);
+        // complete characters not forwarded yet: the held-back bytes, then
+        // a prefix of `buf`
+        let ready = self.validator.valid_up_to() - self.written;
+        let from_buf = ready.saturating_sub(held_len);
+        if ready > 0 {
+            // `buf` is taken now that the validator has seen it; a failure
+            // of `inner` shows on the next call
+            let parts = [&held[..held_len.min(ready)], &buf[..from_buf]];
+            for part in parts {
+                if !self.unforwarded.is_empty() {
+                    self.unforwarded.extend_from_slice(part);
+                    continue;
+                }
+                let (rest, _) = self.forward(part);
+                self.unforwarded.extend_from_slice(rest);
+            }
+            self.written += ready;
+        }
+        match result {
+            Ok(()) => Ok(buf.len()),
+            Err(error) if from_buf == 0 => Err(error.into()),
+            // report the error on the next write, which starts at it
+            Err(_) => Ok(from_buf),
+        }
+    }
+
+    // Fails after invalid input or while a partial character is held back.
+    fn flush(&mut self) -> io::Result<()> {
+        self.forward_unforwarded()?;
+        if let Some(error) = self.flush_error() {
+            return Err(error.into());
+        }
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,104 @@
+use Janus::ambiguity::{AmbiguityReport, ambiguity_report};
+use Janus::encoding::Encoding;
+
+const ALL_THREE: [Encoding; 3] = [Encoding::Gb2312, Encoding::EucKr, Encoding::EucJp];
+
+fn texts(report: &AmbiguityReport) -> Vec<(Encoding, String)> {
+    report
+        .candidates
+        .iter()
+        .map(|candidate| (candidate.encoding, candidate.text()))
+        .collect()
+}
+
+fn rare_flags(report: &AmbiguityReport) -> Vec<Vec<bool>> {
+    report
+        .candidates
+        .iter()
+        .map(|candidate| candidate.chars.iter().map(|decoded| decoded.rare).collect())
+        .collect()
+}
+
+#[test]
+fn decodings_agree() {
+    // ASCII, and the ideographic space and comma, which open row 1 of all
+    // three
+    for data in [&b"abc"[..], b"a\xA1\xA1\xA1\xA2z"] {
+        let report = ambiguity_report(data);
+        assert!(report.is_ambiguous());
+        let texts = texts(&report);
+        assert_eq!(texts.len(), 3);
+        assert!(
+            texts.iter().all(|(_, text)| *text == texts[0].1),
+            "{texts:?}"
+        );
+        assert_eq!(report.diverging_span, None);
+        assert!(report.candidates.iter().all(|c| c.rare_count() == 0));
+    }
+    assert_eq!(ambiguity_report(b"").diverging_span, None);
+}
+
+#[test]
+fn decodings_diverge() {
+    let report = ambiguity_report(b"a\xA1\xA1\xB0\xA1\xB0\xA2");
+    assert_eq!(
+        texts(&report),
+        [
+            (Encoding::Gb2312, "a\u{3000}啊阿".to_string()),
+            (Encoding::EucKr, "a\u{3000}가각".to_string()),
+            (Encoding::EucJp, "a\u{3000}亜唖".to_string()),
+        ]
+    );
+    // from the first pair that reads differently
+    assert_eq!(report.diverging_span, Some(3..5));
+
+    // kana read the same in GB2312 and EUC-JP, and as jamo in EUC-KR
+    let report = ambiguity_report(b"\xA4\xA2\xB0\xA1");
+    let first: Vec<char> = report.candidates.iter().map(|c| c.chars[0].ch).collect();
+    assert_eq!(first, ['あ', 'ㄲ', 'あ']);
+    assert_eq!(report.diverging_span, Some(0..2));
+
+    // a single candidate has nothing to diverge from
+    let report = ambiguity_report(b"\x8E\xB1\xB0\xA1");
+    assert_eq!(texts(&report), [(Encoding::EucJp, "ｱ亜".to_string())]);
+    assert!(!report.is_ambiguous());
+    assert_eq!(report.diverging_span, None);
+
+    let report = ambiguity_report(b"\xFF");
+    assert!(report.candidates.is_empty());
+    assert_eq!(
+        report.to_string(),
+        "not valid in GB2312, EUC-KR or EUC-JP\n"
+    );
+}
+
+// The same bytes fall in an everyday row of one repertoire and a rare one
+// of another, for each pair of the three.
+#[test]
+fn rare_rows() {
+    for (data, rare) in [
+        // hangul, level 1 hanzi and level 1 kanji
+        (&b"\xB0\xA1"[..], [false, false, false]),
+        // level 1 hanzi, hanja and level 2 kanji
+        (b"\xD0\xA1", [false, true, true]),
+        // level 1 hanzi, hanja and level 1 kanji
+        (b"\xCA\xA1", [false, true, false]),
+        // kana in GB2312 and EUC-JP, jamo in EUC-KR
+        (b"\xA4\xA2", [true, true, false]),
+        // pinyin, Latin and box drawing
+        (b"\xA8\xA1", [true, true, true]),
+    ] {
+        let report = ambiguity_report(data);
+        let encodings: Vec<Encoding> = report.candidates.iter().map(|c| c.encoding).collect();
+        assert_eq!(encodings, ALL_THREE, "{data:02x?}");
+        let flags: Vec<Vec<bool>> = rare.iter().map(|&rare| vec![rare]).collect();
+        assert_eq!(rare_flags(&report), flags, "{data:02x?}");
+    }
+
+    let report = ambiguity_report(b"\xB0\xA1\xD0\xA1");
+    assert_eq!(
+        report.to_string(),
+        "GB2312: 啊小 (0 rare)\nEUC-KR: 가[鬼] (1 rare)\nEUC-JP: 亜[弌] (1 rare)\n\
+         diverges at bytes 0..2\n"
+    );
+}
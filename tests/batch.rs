@@ -0,0 +1,63 @@
+mod common;
+
+use Janus::batch::{validate_batch, validate_batch_errors};
+use Janus::encoding::Encoding;
+use common::{Rng, corrupt, sample};
+
+fn assert_matches_per_value(values: &[&[u8]], encoding: Encoding) {
+    let errors: Vec<_> = values
+        .iter()
+        .map(|value| encoding.validate(value).err())
+        .collect();
+    assert_eq!(
+        validate_batch_errors(values, encoding),
+        errors,
+        "{encoding:?}"
+    );
+    let valid: Vec<bool> = errors.iter().map(Option::is_none).collect();
+    assert_eq!(validate_batch(values, encoding), valid, "{encoding:?}");
+}
+
+#[test]
+fn batch_matches_per_value() {
+    let mut rng = Rng(0xBF58_476D_1CE4_E5B9);
+    for encoding in Encoding::ALL {
+        for _ in 0..50 {
+            let owned: Vec<Vec<u8>> = (0..rng.below(40))
+                .map(|_| {
+                    let pieces = rng.below(6);
+                    let mut value = sample(&mut rng, encoding, pieces);
+                    match rng.below(6) {
+                        0 => corrupt(&mut rng, &mut value),
+                        // a lead byte left at the end
+                        1 => value.push(rng.byte(0x81, 0xFE)),
+                        2 => value.insert(rng.below(value.len() + 1), 0),
+                        _ => {}
+                    }
+                    value
+                })
+                .collect();
+            let values: Vec<&[u8]> = owned.iter().map(Vec::as_slice).collect();
+            assert_matches_per_value(&values, encoding);
+        }
+    }
+}
+
+#[test]
+fn fixed_values() {
+    let values: [&[u8]; 6] = [
+        b"",
+        b"plain",
+        &[0xB0, 0xA1, 0x00, 0xB0, 0xA1],
+        &[b'a', 0xB0],
+        &[0xB0, 0xA1, 0xB0],
+        &[0xFF],
+    ];
+    for encoding in Encoding::ALL {
+        assert_matches_per_value(&values, encoding);
+    }
+    let errors = validate_batch_errors(&values, Encoding::Gb2312);
+    let offsets: Vec<_> = errors.iter().map(|e| e.map(|e| e.offset)).collect();
+    assert_eq!(offsets, [None, None, None, Some(1), Some(2), Some(0)]);
+    assert_eq!(validate_batch(&[], Encoding::Gb2312), Vec::<bool>::new());
+}
@@ -0,0 +1,185 @@
+mod common;
+
+use Janus::encoding::Strategy;
+use common::TempDir;
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+fn janus(args: &[&str], input: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_janus"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn detect_ascii_is_undecided() {
+    for input in [&b"hello\n"[..], b""] {
+        let output = janus(&["detect", "--json"], input);
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(stdout.contains("\"best\":null"), "{}", stdout);
+        assert_eq!(output.status.code(), Some(1));
+    }
+}
+
+#[test]
+fn detect_kana() {
+    let (data, _, _) = encoding_rs::EUC_JP.encode("ありがとう");
+    let output = janus(&["detect"], &data);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.starts_with("(standard input): EUC-JP\n"),
+        "{}",
+        stdout
+    );
+    assert_eq!(output.status.code(), Some(0));
+}
+
+// The exit status, standard output and standard error of a run.
+fn run(args: &[&str], input: &[u8]) -> (Option<i32>, String, String) {
+    let output = janus(args, input);
+    (
+        output.status.code(),
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+#[test]
+fn validate_exit_codes() {
+    let dir = TempDir::new("cli-validate");
+    let good = dir.write("good.txt", b"\xB0\xA1 ok");
+    let bad = dir.write("bad.txt", b"ok \xB0\x20");
+    let missing = dir.path().join("missing.txt");
+    let [good, bad, missing] = [&good, &bad, &missing].map(|path| path.to_str().unwrap());
+
+    let (status, stdout, _) = run(&["validate", "-e", "gb2312", good], b"");
+    assert_eq!(status, Some(0));
+    assert_eq!(stdout, format!("{good}: valid\n"));
+
+    // every input is reported; the worst outcome sets the status
+    let (status, stdout, _) = run(&["validate", "-e", "gb2312", good, bad], b"");
+    assert_eq!(status, Some(1));
+    assert_eq!(
+        stdout,
+        format!("{good}: valid\n{bad}: invalid GB2312 at byte 3: invalid trail byte\n")
+    );
+    let (status, stdout, stderr) = run(&["validate", "-e", "gb2312", missing, bad, good], b"");
+    assert_eq!(status, Some(2));
+    assert_eq!(stdout.lines().count(), 2);
+    assert!(
+        stderr.starts_with(&format!("janus: {missing}: ")),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn validate_stdin() {
+    let (data, _, _) = encoding_rs::EUC_JP.encode("標準入力");
+    let broken = [&data[..2], b"\xFF", &data[2..]].concat();
+    for args in [
+        &["validate", "-e", "euc-jp"][..],
+        &["validate", "-e", "euc-jp", "-"],
+    ] {
+        let (status, stdout, _) = run(args, &data);
+        assert_eq!(status, Some(0));
+        assert_eq!(stdout, "(standard input): valid\n");
+        let (status, stdout, _) = run(args, &broken);
+        assert_eq!(status, Some(1));
+        assert_eq!(
+            stdout,
+            "(standard input): invalid EUC-JP at byte 2: invalid lead byte\n"
+        );
+    }
+
+    // a file and the standard input, in the order given
+    let dir = TempDir::new("cli-stdin");
+    let file = dir.write("file.txt", b"plain");
+    let file = file.to_str().unwrap();
+    let (status, stdout, _) = run(&["validate", "-e", "euc-jp", file, "-"], &data[..7]);
+    assert_eq!(status, Some(1));
+    assert_eq!(
+        stdout,
+        format!("{file}: valid\n(standard input): invalid EUC-JP at byte 6: truncated character\n")
+    );
+
+    // with --context the standard input is read into memory
+    let (status, stdout, _) = run(&["validate", "-C", "-e", "euc-jp"], &broken);
+    assert_eq!(status, Some(1));
+    assert!(stdout.contains("\n  --> line 1, column 2\n"), "{stdout}");
+}
+
+// Each way of giving the strategy selects it, whatever the case, and every
+// strategy reaches the same verdict.
+#[test]
+fn validate_strategy() {
+    for strategy in Strategy::ALL {
+        let name = strategy.name();
+        let long = format!("--strategy={name}");
+        let short = format!("-s{}", name.to_uppercase());
+        for args in [
+            &["--strategy", name][..],
+            &[&long],
+            &["-s", name],
+            &[&short],
+            // the last one given wins
+            &["-s", "nonsense", "-s", name],
+        ] {
+            let args: Vec<&str> = ["validate", "-e", "gb18030"]
+                .iter()
+                .chain(args)
+                .copied()
+                .collect();
+            let (status, stdout, _) = run(&args, b"\x81\x30\x81\x30");
+            assert_eq!(status, Some(0), "{args:?}");
+            assert_eq!(stdout, "(standard input): valid\n");
+            let (status, stdout, _) = run(&args, b"\x81\x30\x81");
+            assert_eq!(status, Some(1), "{args:?}");
+            assert_eq!(
+                stdout,
+                "(standard input): invalid GB18030 at byte 0: truncated character\n"
+            );
+        }
+    }
+}
+
+#[test]
+fn validate_usage_errors() {
+    for (args, message) in [
+        (&["validate"][..], "missing --encoding"),
+        (&["validate", "-e", "latin1"], "unknown encoding latin1"),
+        (
+            &["validate", "--encoding=utf-32"],
+            "unknown encoding utf-32",
+        ),
+        (
+            &["validate", "-e", "utf-8", "-s", "fast"],
+            "unknown strategy fast",
+        ),
+        (
+            &["validate", "-e", "utf-8", "--strategy"],
+            "option --strategy needs a value",
+        ),
+        (
+            &["validate", "-e", "utf-8", "--json"],
+            "unknown option --json",
+        ),
+        (
+            &["validate", "-e", "utf-8", "-C=yes"],
+            "option --context takes no value",
+        ),
+    ] {
+        let (status, stdout, stderr) = run(args, b"valid");
+        assert_eq!(status, Some(2), "{args:?}");
+        assert_eq!(stdout, "");
+        assert_eq!(
+            stderr,
+            format!("janus: {message}\nrun 'janus --help' for usage\n")
+        );
+    }
+}
@@ -0,0 +1,48 @@
+mod common;
+
+use Janus::column::validate_column;
+use Janus::encoding::Encoding;
+use common::{Rng, corrupt, sample};
+
+#[test]
+fn column_matches_row_by_row() {
+    let mut rng = Rng(0xA076_1D64_78BD_642F);
+    for encoding in Encoding::ALL {
+        for round in 0..400 {
+            let rows = rng.below(12);
+            let mut values = b"prefix".to_vec();
+            let mut offsets = vec![values.len() as u32];
+            for _ in 0..rows {
+                let pieces = rng.below(8);
+                let mut row = sample(&mut rng, encoding, pieces);
+                if rng.below(4) == 0 {
+                    corrupt(&mut rng, &mut row);
+                }
+                values.extend(row);
+                offsets.push(values.len() as u32);
+            }
+            // the bytes after the last offset are not in any row
+            values.extend(sample(&mut rng, encoding, 2));
+            if round % 2 == 1 {
+                // offsets at any byte, so rows may split characters
+                for offset in &mut offsets {
+                    *offset = rng.below(values.len() + 1) as u32;
+                }
+                offsets.sort();
+            }
+            let validity = validate_column(&values, &offsets, encoding).unwrap();
+            assert_eq!(validity.len(), rows);
+            for row in 0..rows {
+                let bytes = &values[offsets[row] as usize..offsets[row + 1] as usize];
+                assert_eq!(
+                    validity.is_valid(row),
+                    encoding.validate(bytes).is_ok(),
+                    "{encoding:?} {values:02x?} {offsets:?} {row}"
+                );
+            }
+            // the same rows with 64-bit offsets
+            let large: Vec<i64> = offsets.iter().map(|&o| o as i64).collect();
+            assert_eq!(validate_column(&values, &large, encoding), Ok(validity));
+        }
+    }
+}
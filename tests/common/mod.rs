@@ -0,0 +1,139 @@
+// Shared by the integration tests; each test crate uses part of it.
+#![allow(dead_code)]
+
+use Janus::encoding::Encoding;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// xorshift64, enough to spread the inputs
+pub struct Rng(pub u64);
+
+impl Rng {
+    pub fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    pub fn byte(&mut self, low: u8, high: u8) -> u8 {
+        low + self.below((high - low) as usize + 1) as u8
+    }
+
+    pub fn pick(&mut self, bytes: &[u8]) -> u8 {
+        bytes[self.below(bytes.len())]
+    }
+}
+
+// Bytes of one character or ASCII run in the shape of `encoding`, valid
+// or not.
+fn piece(rng: &mut Rng, encoding: Encoding) -> Vec<u8> {
+    if rng.below(4) == 0 {
+        // long runs take the block copy of the decoders
+        let len = 1 + rng.below(40);
+        return (0..len).map(|_| rng.byte(0x20, 0x7E)).collect();
+    }
+    match encoding {
+        Encoding::Gb2312 | Encoding::EucKr => vec![rng.byte(0xA1, 0xFE), rng.byte(0xA1, 0xFE)],
+        Encoding::Gb18030 => match rng.below(3) {
+            0 => vec![
+                rng.byte(0x81, 0x84),
+                rng.byte(0x30, 0x39),
+                rng.byte(0x81, 0xFE),
+                rng.byte(0x30, 0x39),
+            ],
+            1 => vec![
+                rng.byte(0x90, 0xE3),
+                rng.byte(0x30, 0x39),
+                rng.byte(0x81, 0xFE),
+                rng.byte(0x30, 0x39),
+            ],
+            _ => vec![rng.byte(0x81, 0xFE), rng.byte(0x40, 0xFE)],
+        },
+        Encoding::EucJp => match rng.below(4) {
+            0 => vec![0x8E, rng.byte(0xA1, 0xDF)],
+            1 => vec![0x8F, rng.byte(0xA1, 0xFE), rng.byte(0xA1, 0xFE)],
+            _ => vec![rng.byte(0xA1, 0xFE), rng.byte(0xA1, 0xFE)],
+        },
+        Encoding::Utf8 | Encoding::Utf16Le | Encoding::Utf16Be => {
+            let c = char::from_u32(rng.below(0x11_0000) as u32).unwrap_or('\u{FFFD}');
+            let mut units = [0; 2];
+            let units = c.encode_utf16(&mut units);
+            match encoding {
+                Encoding::Utf8 => c.to_string().into_bytes(),
+                Encoding::Utf16Le => units.iter().flat_map(|u| u.to_le_bytes()).collect(),
+                _ => units.iter().flat_map(|u| u.to_be_bytes()).collect(),
+            }
+        }
+    }
+}
+
+// Up to `pieces` pieces of `encoding`, mostly valid ones, so that inputs
+// often run a long way before their first error.
+pub fn sample(rng: &mut Rng, encoding: Encoding, pieces: usize) -> Vec<u8> {
+    let mut data = Vec::new();
+    for _ in 0..pieces {
+        let piece = piece(rng, encoding);
+        if rng.below(16) == 0 || encoding.validate(&piece).is_ok() {
+            data.extend(piece);
+        }
+    }
+    data
+}
+
+// Valid input of at least `len` bytes, made of one sample repeated.
+pub fn long_input(rng: &mut Rng, encoding: Encoding, len: usize) -> Vec<u8> {
+    let mut base = sample(rng, encoding, 4000);
+    if let Err(error) = encoding.validate(&base) {
+        base.truncate(error.offset);
+    }
+    base.repeat(len.div_ceil(base.len()))
+}
+
+// Breaks one byte of `data`, or cuts it short.
+pub fn corrupt(rng: &mut Rng, data: &mut Vec<u8>) {
+    if data.is_empty() {
+        return;
+    }
+    let at = rng.below(data.len());
+    match rng.below(3) {
+        0 => data.truncate(at),
+        1 => data[at] = rng.byte(0x80, 0xFF),
+        _ => data[at] = rng.byte(0x00, 0x7F),
+    }
+}
+
+// A directory under the system temporary directory, removed on drop.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    // `name` keeps the tests of one crate apart, the process id the runs.
+    pub fn new(name: &str) -> TempDir {
+        let path = std::env::temp_dir().join(format!("janus-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    // Writes `data` to `relative`, creating its directories.
+    pub fn write(&self, relative: &str, data: &[u8]) -> PathBuf {
+        let path = self.0.join(relative);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, data).unwrap();
+        path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
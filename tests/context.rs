@@ -0,0 +1,127 @@
+use Janus::context::error_context;
+use Janus::encoding::Encoding;
+
+fn render(encoding: Encoding, data: &[u8]) -> String {
+    let error = encoding.validate(data).unwrap_err();
+    error_context(data, &error).to_string()
+}
+
+// The input of the README example: twelve lines, then a line of 300
+// characters before the error, 293 of them two bytes long.
+fn broken() -> Vec<u8> {
+    let mut text = String::new();
+    for _ in 0..12 {
+        text += &"x".repeat(36);
+        text.push('\n');
+    }
+    text += "       ";
+    text += &"一".repeat(270);
+    text += "中却有些乐事，但不能永远依恃；况又有‘美中不足";
+    let (head, _, _) = encoding_rs::GBK.encode(&text);
+    let (tail, _, _) = encoding_rs::GBK.encode("好事多磨’八个字紧相连属，瞬息间在");
+    [&head[..], b"\xA3A", &tail[..]].concat()
+}
+
+const BROKEN: &str = "\
+invalid GB2312 at byte 1037: invalid trail byte
+  --> line 13, column 301
+000003e0  d0 c8 b4 d3 d0 d0 a9 c0  d6 ca c2 a3 ac b5 ab b2  |................|
+000003f0  bb c4 dc d3 c0 d4 b6 d2  c0 ca d1 a3 bb bf f6 d3  |................|
+00000400  d6 d3 d0 a1 ae c3 c0 d6  d0 b2 bb d7 e3 a3 41 ba  |..............A.|
+                                                  ^^ ^^
+00000410  c3 ca c2 b6 e0 c4 a5 a1  af b0 cb b8 f6 d7 d6 bd  |................|
+00000420  f4 cf e0 c1 ac ca f4 a3  ac cb b2 cf a2 bc e4 d4  |................|
+  text: 但不能永远依恃；况又有‘美中不足�好事多磨’八个字紧相连属，瞬息间";
+
+#[test]
+fn readme_example() {
+    let data = broken();
+    let error = Encoding::Gb2312.validate(&data).unwrap_err();
+    let context = error_context(&data, &error);
+    assert_eq!((context.line, context.column), (13, 301));
+    assert_eq!(context.bad, 1037..1039);
+    assert_eq!(context.dump_start, 0x3E0);
+    assert_eq!(context.to_string(), BROKEN);
+    let readme = include_str!("../README.md");
+    assert!(readme.contains(&format!("broken.txt: {BROKEN}\n")));
+}
+
+#[test]
+fn columns_count_characters() {
+    // two bytes a character, and lines that end at a newline unit
+    let data = b"a\x00\n\x00\x2D\x4E\x62\x00\x00\xDC\x63\x00";
+    assert_eq!(
+        render(Encoding::Utf16Le, data),
+        "\
+invalid UTF-16LE at byte 8: invalid surrogate
+  --> line 2, column 3
+00000000  61 00 0a 00 2d 4e 62 00  00 dc 63 00              |a...-Nb...c.|
+                                   ^^ ^^
+  text: 中b�c"
+    );
+
+    // one to four bytes a character
+    let mut data = "aé\n中😀b".as_bytes().to_vec();
+    data.extend_from_slice(b"\xC0\xAFz");
+    let error = Encoding::Utf8.validate(&data).unwrap_err();
+    let context = error_context(&data, &error);
+    assert_eq!((context.line, context.column), (2, 4));
+    assert_eq!((&*context.before, &*context.after), ("中😀b", ""));
+}
+
+// Carets follow the offending bytes onto the next row, past the gap in
+// the middle of each row.
+#[test]
+fn bad_bytes_across_rows() {
+    let mut data = b"0123456789abcde\xB0 tail".to_vec();
+    data.extend_from_slice(&[b'.'; 35]);
+    assert_eq!(
+        render(Encoding::Gb2312, &data),
+        "\
+invalid GB2312 at byte 15: invalid trail byte
+  --> line 1, column 16
+00000000  30 31 32 33 34 35 36 37  38 39 61 62 63 64 65 b0  |0123456789abcde.|
+                                                        ^^
+00000010  20 74 61 69 6c 2e 2e 2e  2e 2e 2e 2e 2e 2e 2e 2e  | tail...........|
+          ^^
+00000020  2e 2e 2e 2e 2e 2e 2e 2e  2e 2e 2e 2e 2e 2e 2e 2e  |................|
+00000030  2e 2e 2e 2e 2e 2e 2e 2e                           |........|
+  text: 0123456789abcde�tail............"
+    );
+}
+
+#[test]
+fn error_at_start() {
+    assert_eq!(
+        render(Encoding::Gb2312, b"\xFFabc"),
+        "\
+invalid GB2312 at byte 0: invalid lead byte
+  --> line 1, column 1
+00000000  ff 61 62 63                                       |.abc|
+          ^^
+  text: �abc"
+    );
+}
+
+#[test]
+fn error_at_end() {
+    // a character cut short by the end of the input
+    assert_eq!(
+        render(Encoding::Gb2312, b"ab\nc\xB0"),
+        "\
+invalid GB2312 at byte 4: truncated character
+  --> line 2, column 2
+00000000  61 62 0a 63 b0                                    |ab.c.|
+                      ^^
+  text: c�"
+    );
+
+    // an offset past the data marks nothing
+    let data = b"ab\nc\xB0";
+    let mut error = Encoding::Gb2312.validate(data).unwrap_err();
+    error.offset = data.len();
+    let context = error_context(data, &error);
+    assert_eq!((context.line, context.column), (2, 3));
+    assert_eq!(context.bad, 5..5);
+    assert!(!context.to_string().contains('^'));
+}
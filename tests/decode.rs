@@ -0,0 +1,48 @@
+mod common;
+
+use Janus::encoding::Encoding;
+use common::{Rng, corrupt, sample};
+
+fn assert_matches_encoding_rs(data: &[u8], encoding: Encoding) {
+    let decoded = encoding.decode_to_utf8(data);
+    match encoding.validate(data) {
+        Ok(()) => {
+            let (text, _) = encoding.rs_encoding().decode_without_bom_handling(data);
+            assert_eq!(decoded.as_deref(), Ok(&*text), "{encoding:?} {data:02x?}");
+        }
+        Err(error) => assert_eq!(decoded, Err(error), "{encoding:?} {data:02x?}"),
+    }
+}
+
+#[test]
+fn decode_to_utf8_matches_encoding_rs() {
+    let mut rng = Rng(0x2545_F491_4F6C_DD1D);
+    for encoding in Encoding::ALL {
+        for round in 0..3000 {
+            let pieces = 1 + rng.below(24);
+            let mut data = sample(&mut rng, encoding, pieces);
+            if round % 2 == 1 {
+                corrupt(&mut rng, &mut data);
+            }
+            assert_matches_encoding_rs(&data, encoding);
+        }
+    }
+}
+
+// Three UTF-8 bytes per two input bytes is the most the output buffer
+// is sized for.
+#[test]
+fn widest_output_fits() {
+    let cases: [(Encoding, &[u8]); 4] = [
+        (Encoding::Gb2312, &[0xA1, 0xA1]),
+        (Encoding::Gb18030, &[0x81, 0x40]),
+        (Encoding::EucJp, &[0x8E, 0xB1]),
+        (Encoding::EucKr, &[0xB0, 0xA1]),
+    ];
+    for (encoding, character) in cases {
+        for count in 0..40 {
+            let data = character.repeat(count);
+            assert_matches_encoding_rs(&data, encoding);
+        }
+    }
+}
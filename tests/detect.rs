@@ -0,0 +1,112 @@
+mod common;
+
+use Janus::detect::{Candidate, detect, detect_among};
+use Janus::encoding::Encoding;
+use common::{Rng, corrupt, long_input, sample};
+
+const BLOCK: usize = 64 * 1024;
+
+// Each candidate has the error of `Encoding::validate`, and the valid ones
+// come first in the given order, then the others, longest valid prefix
+// first and in the given order on ties.
+fn assert_detects(data: &[u8], encodings: &[Encoding]) {
+    let candidates = detect_among(data, encodings);
+    assert_eq!(candidates.len(), encodings.len());
+    for candidate in &candidates {
+        assert_eq!(
+            candidate.error,
+            candidate.encoding.validate(data).err(),
+            "{} of {} bytes",
+            candidate.encoding,
+            data.len()
+        );
+    }
+    let rank = |candidate: &Candidate| {
+        let index = encodings.iter().position(|&e| e == candidate.encoding);
+        let offset = candidate.error.map_or(usize::MAX, |error| error.offset);
+        (usize::MAX - offset, index)
+    };
+    assert!(
+        candidates
+            .windows(2)
+            .all(|pair| rank(&pair[0]) < rank(&pair[1])),
+        "{candidates:?}"
+    );
+}
+
+#[test]
+fn detect_matches_validate() {
+    let mut rng = Rng(0x2545_F491_4F6C_DD1D);
+    let mut reversed = Encoding::ALL;
+    reversed.reverse();
+    for encoding in Encoding::ALL {
+        for _ in 0..200 {
+            let pieces = 1 + rng.below(40);
+            let mut data = sample(&mut rng, encoding, pieces);
+            if rng.below(2) == 0 {
+                corrupt(&mut rng, &mut data);
+            }
+            assert_detects(&data, &Encoding::ALL);
+            assert_detects(&data, &reversed);
+        }
+    }
+    assert_detects(b"", &Encoding::ALL);
+    assert!(detect(b"").iter().all(Candidate::is_valid));
+}
+
+// Input over several blocks, valid in its own encoding, with errors on
+// both sides of the first block boundary and characters cut off by it.
+#[test]
+fn errors_around_block_boundaries() {
+    let mut rng = Rng(0x9FB2_1C65_1E98_DF25);
+    for encoding in Encoding::ALL {
+        let valid = long_input(&mut rng, encoding, 3 * BLOCK);
+        assert_detects(&valid, &Encoding::ALL);
+        assert!(
+            detect(&valid)
+                .iter()
+                .any(|candidate| candidate.encoding == encoding && candidate.is_valid())
+        );
+
+        let mut past = 0;
+        for at in BLOCK - 3..BLOCK + 4 {
+            for byte in [0x00, 0x80, 0xD8, 0xFF] {
+                let mut data = valid.clone();
+                data[at] = byte;
+                assert_detects(&data, &Encoding::ALL);
+                if let Err(error) = encoding.validate(&data) {
+                    past += (error.offset >= BLOCK) as usize;
+                }
+            }
+            assert_detects(&valid[..at], &Encoding::ALL);
+        }
+        assert!(past > 0, "{encoding}");
+        assert_detects(&valid[..2 * BLOCK + 1], &Encoding::ALL);
+    }
+}
+
+#[test]
+fn valid_candidates_come_first() {
+    // GB2312 and EUC-KR share this pair, which is no valid UTF-8 and, with
+    // an odd length, no UTF-16
+    let data = b"\xB0\xA1a";
+    let candidates = detect(data);
+    let encodings: Vec<Encoding> = candidates.iter().map(|c| c.encoding).collect();
+    assert_eq!(
+        encodings[..4],
+        [
+            Encoding::Gb2312,
+            Encoding::Gb18030,
+            Encoding::EucJp,
+            Encoding::EucKr
+        ]
+    );
+    assert!(candidates[..4].iter().all(Candidate::is_valid));
+    // UTF-8 fails at the first byte, the UTF-16s at the end
+    assert_eq!(
+        encodings[4..],
+        [Encoding::Utf16Le, Encoding::Utf16Be, Encoding::Utf8]
+    );
+    assert_eq!(candidates[4].error.unwrap().offset, 2);
+    assert_eq!(candidates[6].error.unwrap().offset, 0);
+}
@@ -0,0 +1,270 @@
+mod common;
+
+use Janus::encode::*;
+use Janus::encoding::Encoding;
+use Janus::euc_jp::{JpProfile, is_jp_range, is_jp_range_profile};
+
+fn characters() -> impl Iterator<Item = char> {
+    (0x80..0x10000)
+        .chain((0x10000..0x110000).step_by(61))
+        .filter_map(char::from_u32)
+}
+
+// Every character that encodes decodes back through encoding_rs, and every
+// one that encoding_rs writes as a sequence the validators accept, and
+// reads back, encodes.
+#[test]
+fn roundtrip_through_encoding_rs() {
+    for encoding in Encoding::ALL {
+        let rs = encoding.rs_encoding();
+        let mut x0212 = 0;
+        for c in characters() {
+            let text = format!("a{}b", c);
+            let (theirs, _, unmappable) = rs.encode(&text);
+            let (back, _) = rs.decode_without_bom_handling(&theirs);
+            let theirs_roundtrip = !unmappable && encoding.is_valid(&theirs) && back == text;
+            match encode(&text, encoding) {
+                Ok(bytes) => {
+                    assert_eq!(
+                        roundtrip_check(&text, &bytes, encoding),
+                        Ok(()),
+                        "{encoding} {c:?}"
+                    );
+                    if bytes[1] == 0x8F && encoding == Encoding::EucJp {
+                        // JIS X 0212, which encoding_rs reads but never writes
+                        x0212 += 1;
+                        assert_eq!(bytes.len(), 5);
+                    } else if theirs_roundtrip {
+                        assert_eq!(bytes, &theirs[..], "{encoding} {c:?}");
+                    }
+                }
+                Err(error) => {
+                    assert!(!theirs_roundtrip, "{encoding} {c:?} {theirs:02x?}");
+                    assert_eq!((error.offset, error.character), (1, c));
+                }
+            }
+        }
+        if encoding == Encoding::EucJp {
+            assert!(x0212 > 5000, "{x0212}");
+        }
+    }
+}
+
+// Every character but the private-use ones whose sequences encoding_rs
+// reads as the characters GB18030-2005 moved there, and U+E5E5, which it
+// does not map.
+#[test]
+fn gb18030_covers_unicode() {
+    let missing: Vec<char> = characters()
+        .filter(|&c| encode(&c.to_string(), Encoding::Gb18030).is_err())
+        .collect();
+    assert_eq!(missing.len(), 19);
+    assert!(
+        missing
+            .iter()
+            .all(|&c| ('\u{E5E5}'..='\u{E864}').contains(&c))
+    );
+    for c in missing {
+        let text = c.to_string();
+        let (bytes, _, _) = encoding_rs::GB18030.encode(&text);
+        let (back, _) = encoding_rs::GB18030.decode_without_bom_handling(&bytes);
+        assert_ne!(back, text);
+    }
+    // the first and last four-byte sequences of the BMP and the
+    // supplementary planes
+    for (text, bytes) in [
+        ("\u{80}", [0x81, 0x30, 0x81, 0x30]),
+        ("\u{FFFF}", [0x84, 0x31, 0xA4, 0x39]),
+        ("\u{10000}", [0x90, 0x30, 0x81, 0x30]),
+        ("\u{10FFFF}", [0xE3, 0x32, 0x9A, 0x35]),
+    ] {
+        assert_eq!(encode(text, Encoding::Gb18030).unwrap(), bytes);
+    }
+}
+
+#[test]
+fn unicode_encodings() {
+    let text = "a中😀";
+    assert_eq!(encode(text, Encoding::Utf8).unwrap(), text.as_bytes());
+    assert_eq!(
+        encode(text, Encoding::Utf16Le).unwrap(),
+        [0x61, 0, 0x2D, 0x4E, 0x3D, 0xD8, 0x00, 0xDE]
+    );
+    assert_eq!(
+        encode(text, Encoding::Utf16Be).unwrap(),
+        [0, 0x61, 0x4E, 0x2D, 0xD8, 0x3D, 0xDE, 0x00]
+    );
+}
+
+// The first character outside the repertoire, at its byte offset in the
+// UTF-8 input, past ASCII and encodable characters of every length.
+#[test]
+fn error_names_the_first_unencodable_character() {
+    for (text, encoding, offset, character) in [
+        ("€", Encoding::Gb2312, 0, '€'),
+        ("abc中文€ü", Encoding::Gb2312, 9, '€'),
+        ("한국어 丂", Encoding::EucKr, 10, '丂'),
+        ("日本ｱ😀x😀", Encoding::EucJp, 9, '😀'),
+        ("丂 한", Encoding::EucJp, 4, '한'),
+        (&format!("{}丂", "x".repeat(20)), Encoding::EucKr, 20, '丂'),
+    ] {
+        let error = encode(text, encoding).unwrap_err();
+        assert_eq!(
+            error,
+            EncodeError {
+                encoding,
+                offset,
+                character
+            },
+            "{text}"
+        );
+    }
+    let error = encode("ab€", Encoding::Gb2312).unwrap_err();
+    assert_eq!(error.to_string(), "U+20AC '€' at byte 2 is not in GB2312");
+}
+
+#[test]
+fn roundtrip_check_reports_mismatches() {
+    let text = "中文";
+    let encoded = encode(text, Encoding::Gb2312).unwrap();
+    assert_eq!(roundtrip_check(text, &encoded, Encoding::Gb2312), Ok(()));
+    assert_eq!(
+        roundtrip_check("中国", &encoded, Encoding::Gb2312),
+        Err(RoundtripError::Mismatch { offset: 3 })
+    );
+    assert!(matches!(
+        roundtrip_check(text, &encoded[..3], Encoding::Gb2312),
+        Err(RoundtripError::Invalid(error)) if error.offset == 2
+    ));
+}
+
+// The errors `encode` would report one character at a time.
+fn unencodable(text: &str, encoding: Encoding) -> Vec<EncodeError> {
+    text.char_indices()
+        .filter_map(|(offset, c)| {
+            let error = encode(&c.to_string(), encoding).err()?;
+            Some(EncodeError { offset, ..error })
+        })
+        .collect()
+}
+
+// `is_encodable` and `find_unencodable` skip ASCII sixteen bytes at a time
+// and test membership without encoding, and must agree with `encode`.
+#[test]
+fn checks_agree_with_encode() {
+    // in some of the repertoires and not in others
+    let characters = [
+        "中", "€", "ü", "丂", "한", "ｱ", "😀", "①", "髙", "\u{E5E5}", "\u{3164}", "·",
+    ];
+    let mut texts = Vec::new();
+    for run in 0..40 {
+        for c in characters {
+            let ascii = "x".repeat(run);
+            texts.push(format!("{ascii}{c}"));
+            texts.push(format!("{ascii}{c}{ascii}{c}y"));
+            texts.push(format!("中{ascii}{c}{}", "y".repeat(17)));
+        }
+    }
+    let mut rng = common::Rng(0xA076_1D64_78BD_642F);
+    for _ in 0..2000 {
+        let mut text = String::new();
+        for _ in 0..rng.below(8) {
+            text += &"a".repeat(rng.below(35));
+            text += characters[rng.below(characters.len())];
+        }
+        texts.push(text);
+    }
+    texts.push(String::new());
+
+    for encoding in Encoding::ALL {
+        for text in &texts {
+            let errors = unencodable(text, encoding);
+            let encoded = encode(text, encoding);
+            assert_eq!(
+                is_encodable(text, encoding),
+                encoded.is_ok(),
+                "{encoding} {text}"
+            );
+            assert_eq!(encoded.err(), errors.first().copied(), "{encoding} {text}");
+            assert_eq!(
+                find_unencodable(text, encoding),
+                errors,
+                "{encoding} {text}"
+            );
+        }
+    }
+}
+
+#[test]
+fn preferences() {
+    for (text, preferences, charset) in [
+        ("", &CHINESE[..], Charset::Gb2312),
+        ("中文", &CHINESE, Charset::Gb2312),
+        ("中文丂", &CHINESE, Charset::Gbk),
+        ("中文😀", &CHINESE, Charset::Gb18030),
+        ("한국", &KOREAN, Charset::EucKr),
+        // not among the 2350 syllables of KS X 1001
+        ("한국똠", &KOREAN, Charset::Cp949),
+        ("日本", &JAPANESE, Charset::EucJp),
+        // JIS X 0212
+        ("日本丂", &JAPANESE, Charset::EucJp),
+        // NEC row 13 and an IBM extension, the README example
+        ("①髙", &JAPANESE, Charset::Cp51932),
+        // both of them and JIS X 0212
+        ("①髙丂", &JAPANESE, Charset::EucJpMs),
+    ] {
+        assert_eq!(
+            best_legacy_encoding(text, preferences),
+            LegacyChoice::Fits(charset),
+            "{text}"
+        );
+        assert_eq!(coverage(text, charset), 100.0);
+        if let Some(&narrower) = preferences.first().filter(|&&first| first != charset) {
+            assert!(coverage(text, narrower) < 100.0, "{text}");
+        }
+    }
+}
+
+#[test]
+fn coverage_of_each_preference() {
+    // ① is NEC row 13, 丂 JIS X 0212 and 한 in none of them
+    assert_eq!(
+        best_legacy_encoding("①丂x한", &JAPANESE),
+        LegacyChoice::Coverage(vec![
+            (Charset::EucJp, 50.0),
+            (Charset::Cp51932, 50.0),
+            (Charset::EucJpMs, 75.0),
+        ])
+    );
+    assert_eq!(
+        best_legacy_encoding("똠😀한국", &KOREAN),
+        LegacyChoice::Coverage(vec![(Charset::EucKr, 50.0), (Charset::Cp949, 75.0)])
+    );
+    assert_eq!(coverage("", Charset::EucKr), 100.0);
+    assert_eq!(coverage("😀", Charset::Gbk), 0.0);
+    assert_eq!(coverage("😀", Charset::Gb18030), 100.0);
+}
+
+// The charsets without an `Encoding` are the ones encoding_rs writes.
+#[test]
+fn encoding_rs_writes_the_supersets() {
+    for (text, charset, encoding, profile) in [
+        ("中文丂", Charset::Gbk, encoding_rs::GBK, None),
+        ("한국똠", Charset::Cp949, encoding_rs::EUC_KR, None),
+        (
+            "①髙",
+            Charset::Cp51932,
+            encoding_rs::EUC_JP,
+            Some(JpProfile::Cp51932),
+        ),
+    ] {
+        assert_eq!(charset.encoding(), None);
+        let (bytes, _, unmappable) = encoding.encode(text);
+        assert!(!unmappable);
+        assert_eq!(encoding.decode_without_bom_handling(&bytes).0, text);
+        if let Some(profile) = profile {
+            assert!(is_jp_range_profile(&bytes, profile));
+            assert!(!is_jp_range(&bytes));
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use Janus::euc_jp::*;
+use Janus::tools::build_i32_table_from_bool;
+
+fn decodes(bytes: &[u8]) -> bool {
+    let (text, malformed) = encoding_rs::EUC_JP.decode_without_bom_handling(bytes);
+    !malformed && text.chars().count() == 1
+}
+
+struct Profile {
+    profile: JpProfile,
+    lookup: Box<[bool; 32768]>,
+    simd: Box<[i32; 32768]>,
+}
+
+impl Profile {
+    fn new(profile: JpProfile) -> Self {
+        let lookup = Box::new(build_jp_profile_table(profile));
+        let simd = Box::new(build_i32_table_from_bool(&lookup));
+        Profile {
+            profile,
+            lookup,
+            simd,
+        }
+    }
+
+    // Whether the profile accepts `bytes`, on which every path agrees.
+    fn accepts(&self, bytes: &[u8]) -> bool {
+        let profile = self.profile;
+        let range = is_jp_range_profile(bytes, profile);
+        assert_eq!(
+            is_jp_lookup_profile(bytes, &self.lookup, profile),
+            range,
+            "lookup {profile:?} {bytes:02x?}"
+        );
+        // after a whole block of ASCII and in the middle of one, so that
+        // both the kernel and the fallback see the sequence
+        for ascii in [16, 23] {
+            let mut data = vec![b'a'; ascii];
+            data.extend(bytes);
+            data.extend([b'a'; 20]);
+            assert_eq!(
+                is_jp_simd_profile(&data, &self.simd, profile),
+                range,
+                "simd {profile:?} {bytes:02x?}"
+            );
+        }
+        range
+    }
+}
+
+fn pairs() -> impl Iterator<Item = (u8, u8)> {
+    (0xA1..=0xFE).flat_map(|lead| (0xA1..=0xFE).map(move |trail| (lead, trail)))
+}
+
+// CP51932 is the JIS X 0208 table of encoding_rs, NEC row 13 and the
+// NEC-selected IBM extensions included, without JIS X 0212.
+#[test]
+fn cp51932_is_the_table_of_encoding_rs() {
+    let cp51932 = Profile::new(JpProfile::Cp51932);
+    for (lead, trail) in pairs() {
+        assert_eq!(
+            cp51932.accepts(&[lead, trail]),
+            decodes(&[lead, trail]),
+            "{lead:02x} {trail:02x}"
+        );
+        assert!(!cp51932.accepts(&[0x8F, lead, trail]));
+    }
+    // ① in row 13, 髙 in row 90
+    assert!(cp51932.accepts(&[0xAD, 0xA1, 0xFB, 0xFC]));
+}
+
+// JIS X 0208 and 0212 as encoding_rs reads them, but for the NEC and IBM
+// rows, which Jis rejects.
+#[test]
+fn jis_rejects_the_vendor_rows() {
+    let jis = Profile::new(JpProfile::Jis);
+    for (lead, trail) in pairs() {
+        let vendor = lead == 0xAD || (0xF9..=0xFC).contains(&lead);
+        assert_eq!(
+            jis.accepts(&[lead, trail]),
+            decodes(&[lead, trail]) && !vendor,
+            "{lead:02x} {trail:02x}"
+        );
+        assert_eq!(
+            jis.accepts(&[0x8F, lead, trail]),
+            decodes(&[0x8F, lead, trail]),
+            "8f {lead:02x} {trail:02x}"
+        );
+    }
+    // the last character of JIS X 0212 and the cell after it
+    assert!(jis.accepts(&[0x8F, 0xED, 0xE3]));
+    assert!(!jis.accepts(&[0x8F, 0xED, 0xE4]));
+}
+
+// eucJP-ms adds to Jis NEC row 13, the IBM extensions at 0x8FF3F3-0x8FF4FE
+// and the user-defined rows 85-94 of both planes, 0xF5A1-0xFEFE and
+// 0x8FF5A1-0x8FFEFE. encoding_rs maps none of them but row 13.
+#[test]
+fn eucjp_ms_adds_the_vendor_rows() {
+    let jis = Profile::new(JpProfile::Jis);
+    let ms = Profile::new(JpProfile::EucJpMs);
+    for (lead, trail) in pairs() {
+        let user_defined = lead >= 0xF5;
+        let expected = if lead == 0xAD {
+            decodes(&[lead, trail])
+        } else {
+            user_defined || jis.accepts(&[lead, trail])
+        };
+        assert_eq!(
+            ms.accepts(&[lead, trail]),
+            expected,
+            "{lead:02x} {trail:02x}"
+        );
+
+        let ibm = (lead, trail) >= (0xF3, 0xF3) && lead <= 0xF4;
+        assert_eq!(
+            ms.accepts(&[0x8F, lead, trail]),
+            ibm || user_defined || jis.accepts(&[0x8F, lead, trail]),
+            "8f {lead:02x} {trail:02x}"
+        );
+    }
+    // ① is in row 13; the NEC-selected rows hold user-defined characters
+    assert!(ms.accepts(&[0xAD, 0xA1, 0xFB, 0xFC]));
+    assert!(!ms.accepts(&[0xAD, 0xBF]));
+}
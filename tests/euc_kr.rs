@@ -0,0 +1,192 @@
+mod common;
+
+use Janus::encoding::Encoding;
+use Janus::euc_kr::*;
+use Janus::tools::build_i32_table_from_bool;
+use common::Rng;
+
+const INITIALS: &[u8] = &[0xA1, 0xA4, 0xB1, 0xBE];
+const MEDIALS: &[u8] = &[0xBF, 0xC8, 0xD3];
+const FINALS: &[u8] = &[0xA1, 0xA9, 0xBE, 0xD4];
+// any jamo, the filler included, for broken sequences
+const JAMO: &[u8] = &[0xA1, 0xA3, 0xB4, 0xBF, 0xD3, 0xD4, 0xD5];
+
+fn push_piece(rng: &mut Rng, out: &mut Vec<u8>) {
+    match rng.below(10) {
+        0 => out.push(rng.pick(b"a0 \n")),
+        // Hangul and hanja, inside and past the end of the KPS 9566 rows
+        1 => out.extend([
+            rng.pick(&[0xB0, 0xCC, 0xFD]),
+            rng.pick(&[0xA1, 0xCF, 0xD0, 0xFE]),
+        ]),
+        // symbols, some of them unassigned
+        2 => out.extend([
+            rng.pick(&[0xA1, 0xA2, 0xA5, 0xAC, 0xAD]),
+            rng.pick(&[0xA1, 0xE8, 0xFE]),
+        ]),
+        // user-defined rows
+        3 => out.extend([rng.pick(&[0xC9, 0xFE]), rng.pick(&[0xA1, 0xD0, 0xFE])]),
+        4..=6 => out.extend([
+            0xA4,
+            0xD4,
+            0xA4,
+            rng.pick(INITIALS),
+            0xA4,
+            rng.pick(MEDIALS),
+            0xA4,
+            rng.pick(FINALS),
+        ]),
+        7 => {
+            out.extend([0xA4, 0xD4]);
+            for _ in 0..rng.below(4) {
+                out.extend([0xA4, rng.pick(JAMO)]);
+            }
+        }
+        8 => out.extend([0xA4, rng.pick(JAMO)]),
+        _ => out.push(rng.pick(&[0x80, 0xA0, 0xA4, 0xFF])),
+    }
+}
+
+fn every_option() -> Vec<Tables> {
+    let mut tables = Vec::new();
+    for composition in [false, true] {
+        for user_defined in [false, true] {
+            for repertoire in [KrRepertoire::KsX1001, KrRepertoire::Kps9566] {
+                tables.push(Tables::new(KrOptions {
+                    composition,
+                    user_defined,
+                    repertoire,
+                }));
+            }
+        }
+    }
+    tables
+}
+
+struct Tables {
+    options: KrOptions,
+    lookup: Box<[bool; 32768]>,
+    simd: Box<[i32; 32768]>,
+}
+
+impl Tables {
+    fn new(options: KrOptions) -> Self {
+        let lookup = Box::new(build_kr_table_with(options));
+        let simd = Box::new(build_i32_table_from_bool(&lookup));
+        Tables {
+            options,
+            lookup,
+            simd,
+        }
+    }
+}
+
+fn assert_paths_agree(data: &[u8], tables: &Tables) {
+    let options = tables.options;
+    let range = is_kr_range_with(data, options);
+    assert_eq!(
+        is_kr_lookup_with(data, &tables.lookup, options),
+        range,
+        "lookup {options:?} {data:02x?}"
+    );
+    assert_eq!(
+        is_kr_simd_with(data, &tables.simd, options),
+        range,
+        "simd {options:?} {data:02x?}"
+    );
+    assert_eq!(
+        find_kr_nonstandard(data, options).is_some(),
+        range,
+        "nonstandard {options:?} {data:02x?}"
+    );
+}
+
+#[test]
+fn paths_agree_under_every_option() {
+    let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+    for tables in every_option() {
+        for _ in 0..4000 {
+            // an ASCII prefix moves the pieces across block boundaries
+            let mut data = vec![b'a'; rng.below(16)];
+            let pieces = 1 + rng.below(12);
+            for _ in 0..pieces {
+                // mostly valid pieces, so the input often survives to the end
+                if rng.below(8) == 0 {
+                    push_piece(&mut rng, &mut data);
+                } else {
+                    let start = data.len();
+                    push_piece(&mut rng, &mut data);
+                    if !is_kr_range_with(&data[start..], tables.options) {
+                        data.truncate(start);
+                    }
+                }
+            }
+            assert_paths_agree(&data, &tables);
+        }
+    }
+}
+
+#[test]
+fn filler_blocks_take_the_fallback() {
+    let broken = [
+        0xA4, 0xD4, 0xA4, 0xA1, 0xA4, 0xA1, 0xA4, 0xA1, 0xB0, 0xA1, 0xB0, 0xA1, 0xB0, 0xA1, 0xB0,
+        0xA1,
+    ];
+    // a sequence ending with a final filler right at a block boundary
+    let mut straddling = vec![0xB0, 0xA1, 0xB0, 0xA1, 0xB0, 0xA1];
+    straddling.extend([0xA4, 0xD4, 0xA4, 0xA1, 0xA4, 0xBF, 0xA4, 0xD4]);
+    straddling.extend([0xB0, 0xA1].repeat(8));
+    for tables in every_option() {
+        assert_paths_agree(&broken, &tables);
+        for shift in 0..16 {
+            let mut data = vec![b'a'; shift];
+            data.extend(&straddling);
+            assert_paths_agree(&data, &tables);
+        }
+    }
+    let composed = KrOptions {
+        composition: true,
+        ..KrOptions::default()
+    };
+    assert!(!is_kr_range_with(&broken, composed));
+    assert!(is_kr_range_with(&straddling, composed));
+}
+
+fn regions(data: &[u8], options: KrOptions) -> Option<Vec<(usize, KrRegion)>> {
+    find_kr_nonstandard(data, options).map(|marks| {
+        marks
+            .iter()
+            .map(|mark| (mark.offset, mark.region))
+            .collect()
+    })
+}
+
+#[test]
+fn nonstandard_marks() {
+    // 가, a user-defined pair and 가 composed from its jamo
+    let data = b"a\xB0\xA1\xC9\xA1\xA4\xD4\xA4\xA1\xA4\xBF\xA4\xD4";
+    let every = KrOptions {
+        composition: true,
+        user_defined: true,
+        repertoire: KrRepertoire::KsX1001,
+    };
+    assert_eq!(
+        regions(data, every),
+        Some(vec![(3, KrRegion::UserDefined), (5, KrRegion::Composed)])
+    );
+    assert_eq!(regions(data, KrOptions::default()), None);
+    assert_eq!(regions(&data[..3], KrOptions::default()), Some(vec![]));
+    assert!(!Encoding::EucKr.is_valid(data));
+
+    // KPS 9566 reads the shared code positions differently too, so every
+    // pair is marked, past the end of KS X 1001 or not
+    let kps = KrOptions {
+        repertoire: KrRepertoire::Kps9566,
+        ..KrOptions::default()
+    };
+    assert_eq!(
+        regions(b"a\xB0\xA1\xC9\xA1", kps),
+        Some(vec![(1, KrRegion::Kps9566), (3, KrRegion::Kps9566)])
+    );
+    assert_eq!(regions(b"\xC9\xA1", KrOptions::default()), None);
+}
@@ -0,0 +1,115 @@
+mod common;
+
+use Janus::encoding::{Encoding, Strategy};
+use Janus::file::{Contents, FileError, validate_file, validate_reader};
+use common::TempDir;
+use std::ffi::CString;
+use std::fs::File;
+use std::io::Write;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::thread;
+
+// The outcome of a validation, comparable across paths.
+fn outcome(result: Result<(), FileError>) -> Result<(), String> {
+    result.map_err(|error| match error {
+        FileError::Io(error) => panic!("{error}"),
+        error => error.to_string(),
+    })
+}
+
+// Validates `data` written into a FIFO, which cannot be mapped.
+fn through_fifo(
+    dir: &TempDir,
+    data: &[u8],
+    encoding: Encoding,
+    strategy: Strategy,
+) -> Result<(), String> {
+    let path = dir.path().join("fifo");
+    let _ = std::fs::remove_file(&path);
+    let name = CString::new(path.as_os_str().as_bytes()).unwrap();
+    assert_eq!(unsafe { libc::mkfifo(name.as_ptr(), 0o600) }, 0);
+    thread::scope(|scope| {
+        let writer = scope.spawn(|| {
+            // the reader may stop at the first error and close its end
+            let _ = File::create(&path).unwrap().write_all(data);
+        });
+        let result = outcome(validate_file(&path, encoding, strategy));
+        writer.join().unwrap();
+        result
+    })
+}
+
+fn mapped(path: &Path) -> bool {
+    matches!(Contents::load(path).unwrap(), Contents::Mapped(_))
+}
+
+#[test]
+fn every_path_agrees() {
+    let dir = TempDir::new("file-paths");
+    let (valid, _, _) = encoding_rs::GBK.encode("文件验证 mmap 一二三");
+    let mut invalid = valid.to_vec();
+    invalid[9] = 0xFF;
+    let mut truncated = valid.to_vec();
+    truncated.push(0xB0);
+
+    for (data, expected) in [
+        (&valid[..], Ok(())),
+        (
+            &invalid,
+            Err("invalid GB2312 at byte 9: invalid lead byte"),
+        ),
+        (
+            &truncated,
+            Err("invalid GB2312 at byte 20: truncated character"),
+        ),
+    ] {
+        let expected = expected.map_err(str::to_string);
+        let path = dir.write("data.txt", data);
+        assert!(mapped(&path));
+        for strategy in Strategy::ALL {
+            assert_eq!(
+                outcome(validate_file(&path, Encoding::Gb2312, strategy)),
+                expected,
+                "{strategy}"
+            );
+            assert_eq!(
+                outcome(validate_reader(data, Encoding::Gb2312, strategy)),
+                expected,
+                "{strategy}"
+            );
+            assert_eq!(
+                through_fifo(&dir, data, Encoding::Gb2312, strategy),
+                expected,
+                "{strategy}"
+            );
+        }
+    }
+}
+
+// Every strategy accepts an empty file, mapped or not, encoding_rs's
+// included.
+#[test]
+fn empty_file_is_valid() {
+    let dir = TempDir::new("file-empty");
+    let path = dir.write("empty.txt", b"");
+    assert!(!mapped(&path));
+    assert_eq!(Contents::load(&path).unwrap().as_slice(), b"");
+    for encoding in Encoding::ALL {
+        for strategy in Strategy::ALL {
+            assert_eq!(outcome(validate_file(&path, encoding, strategy)), Ok(()));
+            assert_eq!(
+                outcome(validate_reader(&b""[..], encoding, strategy)),
+                Ok(())
+            );
+            assert_eq!(through_fifo(&dir, b"", encoding, strategy), Ok(()));
+        }
+    }
+}
+
+#[test]
+fn missing_file_is_io_error() {
+    let dir = TempDir::new("file-missing");
+    let result = validate_file(dir.path().join("missing"), Encoding::Utf8, Strategy::Simd);
+    assert!(matches!(result, Err(FileError::Io(_))));
+}
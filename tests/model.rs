@@ -0,0 +1,57 @@
+use Janus::encoding::Encoding;
+use Janus::model::ModelScorer;
+use Janus::semval::{self, SemanticScorer};
+
+const CANDIDATES: [Encoding; 4] = [
+    Encoding::Gb2312,
+    Encoding::Gb18030,
+    Encoding::EucJp,
+    Encoding::EucKr,
+];
+
+// Kana are valid GB2312 (row 0xA4) and decode to the same text there, but
+// only the EUC-JP model knows them.
+#[test]
+fn kana_read_as_euc_jp() {
+    let scorer = ModelScorer::default();
+    for text in [
+        "あい",
+        "ありがとう",
+        "これはペンです。わたしはがくせいです。",
+    ] {
+        let (data, _, _) = encoding_rs::EUC_JP.encode(text);
+        let readings = scorer.readings(&data, &CANDIDATES);
+        assert_eq!(readings[0].encodings[0], Encoding::EucJp, "{}", text);
+        assert!(readings[0].encodings.contains(&Encoding::Gb2312));
+        assert!(readings[0].confidence > 0.99, "{}", text);
+
+        let alone = scorer.readings(&data, &[Encoding::EucJp]);
+        assert_eq!(readings[0].log_likelihood, alone[0].log_likelihood);
+    }
+}
+
+#[test]
+fn hanzi_read_as_gb2312() {
+    let scorer = ModelScorer::default();
+    let (data, _, _) = encoding_rs::GBK.encode("中华人民共和国成立了");
+    let readings = scorer.readings(&data, &CANDIDATES);
+    assert_eq!(readings[0].encodings, [Encoding::Gb2312, Encoding::Gb18030]);
+}
+
+// ASCII read out of UTF-16 code units leaves the models nothing to score,
+// and must not pass for the most plausible text.
+#[test]
+fn utf16_ascii_is_not_plausible() {
+    let scorer = ModelScorer::default();
+    let data: Vec<u8> = "hello".encode_utf16().flat_map(u16::to_le_bytes).collect();
+    assert_eq!(scorer.score(Encoding::Utf16Le, &data), f64::NEG_INFINITY);
+    assert_eq!(scorer.score(Encoding::Gb2312, &data), 0.0);
+    let ranked = semval::rank(&data, &scorer);
+    assert!(
+        ranked
+            .iter()
+            .any(|scored| scored.encoding == Encoding::Utf16Le)
+    );
+    assert_eq!(ranked[0].encoding, Encoding::Gb2312);
+    assert_eq!(ranked.last().unwrap().encoding, Encoding::Utf16Le);
+}
@@ -0,0 +1,96 @@
+mod common;
+
+use Janus::encoding::Encoding;
+use Janus::multi::{self, build_multi_table, multi_valid_encodings, multi_valid_mask};
+use common::Rng;
+
+const ENCODINGS: [(u8, Encoding); 3] = [
+    (multi::GB2312, Encoding::Gb2312),
+    (multi::EUC_KR, Encoding::EucKr),
+    (multi::EUC_JP, Encoding::EucJp),
+];
+
+// Pairs grouped by the encodings that accept them, indexed by mask.
+fn pairs_by_mask(table: &[i32; 32768]) -> Vec<Vec<[u8; 2]>> {
+    let mut pairs = vec![Vec::new(); 8];
+    for lead in 0xA1..=0xFE {
+        for trail in 0xA1..=0xFE {
+            let mask = table[(lead - 0x80) as usize * 256 + trail as usize];
+            pairs[mask as usize].push([lead, trail]);
+        }
+    }
+    pairs
+}
+
+fn push_piece(rng: &mut Rng, pairs: &[Vec<[u8; 2]>], out: &mut Vec<u8>) {
+    match rng.below(12) {
+        0 => out.extend((0..rng.below(24)).map(|_| rng.byte(0x20, 0x7E))),
+        // half-width katakana, EUC-JP only
+        1 => out.extend([0x8E, rng.byte(0xA0, 0xE0)]),
+        // JIS X 0212, EUC-JP only
+        2 => out.extend([0x8F, rng.byte(0xA1, 0xFE), rng.byte(0xA1, 0xFE)]),
+        3 => out.push(rng.byte(0x80, 0xFF)),
+        // mostly pairs that all three accept
+        4..=7 => out.extend(pairs[multi::ALL as usize][rng.below(pairs[7].len())]),
+        _ => {
+            let mask = 1 + rng.below(7);
+            out.extend(pairs[mask][rng.below(pairs[mask].len())]);
+        }
+    }
+}
+
+// The combined pass accepts what the three validators do, whichever of
+// them fails first and wherever each one fails.
+#[test]
+fn multi_matches_separate_validators() {
+    let table = build_multi_table();
+    let pairs = pairs_by_mask(&table);
+    for (mask, pairs) in pairs.iter().enumerate() {
+        assert!(!pairs.is_empty() || mask == 0, "{mask}");
+    }
+
+    let mut rng = Rng(0x5851_F42D_4C95_7F2D);
+    // the error offsets met for each encoding, by position in a block
+    let mut offsets = [[false; 16]; 3];
+    for _ in 0..20000 {
+        let mut data = Vec::new();
+        let pieces = 1 + rng.below(24);
+        for _ in 0..pieces {
+            push_piece(&mut rng, &pairs, &mut data);
+        }
+        let mut expected = 0;
+        for (e, &(bit, encoding)) in ENCODINGS.iter().enumerate() {
+            match encoding.validate(&data) {
+                Ok(()) => expected |= bit,
+                Err(error) => offsets[e][error.offset % 16] = true,
+            }
+        }
+        assert_eq!(multi_valid_mask(&data, &table), expected, "{data:02x?}");
+        assert_eq!(
+            multi_valid_encodings(&data),
+            multi::encodings(expected),
+            "{data:02x?}"
+        );
+    }
+    assert_eq!(offsets, [[true; 16]; 3]);
+}
+
+#[test]
+fn single_encoding_left() {
+    let table = build_multi_table();
+    let pairs = pairs_by_mask(&table);
+    for (bit, encoding) in ENCODINGS {
+        let own = pairs[bit as usize][0];
+        // the others fail in the first block, the survivor in a later one
+        for at in [0, 15, 16, 17, 40] {
+            let mut data = vec![b'a'; at];
+            data.extend(own);
+            data.extend([b'b'; 40]);
+            assert_eq!(multi_valid_mask(&data, &table), bit, "{encoding} {at}");
+            data.push(0xFF);
+            assert!(encoding.validate(&data).is_err());
+            assert_eq!(multi_valid_mask(&data, &table), 0, "{encoding} {at}");
+        }
+    }
+    assert_eq!(multi_valid_mask(b"", &table), multi::ALL);
+}
@@ -0,0 +1,40 @@
+mod common;
+
+use Janus::encoding::Encoding;
+use Janus::parallel::validate_parallel;
+use common::{Rng, long_input};
+
+#[test]
+fn parallel_matches_sequential() {
+    let mut rng = Rng(0xD1B5_4A32_D192_ED03);
+    for encoding in Encoding::ALL {
+        let valid = long_input(&mut rng, encoding, 3 << 20);
+        for threads in [2, 3] {
+            assert_eq!(validate_parallel(&valid, encoding, threads), Ok(()));
+            // errors around the chunk boundaries, and one anywhere else
+            let mut at: Vec<usize> = (1..threads)
+                .map(|chunk| valid.len() * chunk / threads)
+                .flat_map(|bound| bound - 1..=bound + 1)
+                .collect();
+            at.push(rng.below(valid.len()));
+            for at in at {
+                let mut data = valid.clone();
+                data[at] = rng.byte(0x80, 0xFF);
+                assert_eq!(
+                    validate_parallel(&data, encoding, threads),
+                    encoding.validate(&data),
+                    "{encoding:?} {threads} {at}"
+                );
+            }
+            // characters cut off at the end
+            for cut in 1..4 {
+                let data = &valid[..valid.len() - cut];
+                assert_eq!(
+                    validate_parallel(data, encoding, threads),
+                    encoding.validate(data),
+                    "{encoding:?} {threads} cut {cut}"
+                );
+            }
+        }
+    }
+}
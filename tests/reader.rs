@@ -0,0 +1,102 @@
+use Janus::encoding::Encoding;
+use Janus::error::ErrorKind;
+use Janus::reader::{ValidatingReader, validation_error};
+use std::io::{self, BufRead, Read};
+
+// Hands out at most `max` bytes per call.
+struct Trickle<'a> {
+    data: &'a [u8],
+    max: usize,
+}
+
+impl Read for Trickle<'_> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        let n = self.data.len().min(self.max).min(out.len());
+        out[..n].copy_from_slice(&self.data[..n]);
+        self.data = &self.data[n..];
+        Ok(n)
+    }
+}
+
+// Reads all of `data` through a validating reader, `out` bytes at a time,
+// and returns the bytes delivered and the error that ended the read.
+fn read_through(
+    data: &[u8],
+    encoding: Encoding,
+    capacity: usize,
+    max: usize,
+    out: usize,
+) -> (Vec<u8>, io::Result<()>) {
+    let inner = Trickle { data, max };
+    let mut reader = ValidatingReader::with_capacity(capacity, inner, encoding);
+    let mut delivered = Vec::new();
+    let mut buf = vec![0; out];
+    loop {
+        match reader.read(&mut buf) {
+            Ok(0) => return (delivered, Ok(())),
+            Ok(n) => delivered.extend_from_slice(&buf[..n]),
+            Err(error) => return (delivered, Err(error)),
+        }
+    }
+}
+
+fn shapes() -> impl Iterator<Item = (usize, usize, usize)> {
+    // buffer capacity, inner read size and caller read size; reads at
+    // least as large as the buffer bypass it
+    [1, 3, 8, 64].into_iter().flat_map(|capacity| {
+        [1, 2, 5, 1000]
+            .into_iter()
+            .flat_map(move |max| [1, 4, 7, 64].map(move |out| (capacity, max, out)))
+    })
+}
+
+#[test]
+fn bytes_pass_through() {
+    let (data, _, _) = encoding_rs::GB18030.encode("验证 reader 😀，一二三四五六七八九十。");
+    for (capacity, max, out) in shapes() {
+        let (delivered, result) = read_through(&data, Encoding::Gb18030, capacity, max, out);
+        assert!(result.is_ok(), "{capacity} {max} {out}");
+        assert_eq!(delivered, *data, "{capacity} {max} {out}");
+    }
+
+    let mut reader = ValidatingReader::new(&data[..], Encoding::Gb18030);
+    let mut delivered = Vec::new();
+    reader.read_to_end(&mut delivered).unwrap();
+    assert_eq!(delivered, *data);
+    assert_eq!(reader.fill_buf().unwrap(), b"");
+}
+
+// The bytes before an error are delivered, then the read fails with the
+// offset of the error in the whole input.
+#[test]
+fn error_offset_is_absolute() {
+    let (data, _, _) = encoding_rs::GBK.encode("一二三四五六七八九十 abc 一二三四五六七八九十");
+    let mut data = data.into_owned();
+    // the lead byte of the fourth hanzi after the ASCII
+    let at = 20 + 5 + 6;
+    data[at] = 0xFF;
+    for (capacity, max, out) in shapes() {
+        let (delivered, result) = read_through(&data, Encoding::Gb2312, capacity, max, out);
+        let error = result.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        let validation = validation_error(&error).unwrap();
+        assert_eq!(validation.offset, at, "{capacity} {max} {out}");
+        assert_eq!(delivered, data[..at], "{capacity} {max} {out}");
+    }
+    assert!(validation_error(&io::Error::other("other")).is_none());
+}
+
+// A last character cut short is only known to be at the end of the input.
+#[test]
+fn truncated_at_eof() {
+    let data = "検証テスト".as_bytes();
+    let cut = &data[..data.len() - 1];
+    for (capacity, max, out) in shapes() {
+        let (delivered, result) = read_through(cut, Encoding::Utf8, capacity, max, out);
+        let error = result.unwrap_err();
+        let validation = validation_error(&error).unwrap();
+        assert_eq!(validation.kind, ErrorKind::Truncated);
+        assert_eq!(validation.offset, data.len() - 3, "{capacity} {max} {out}");
+        assert_eq!(delivered, cut, "{capacity} {max} {out}");
+    }
+}
@@ -0,0 +1,183 @@
+mod common;
+
+use Janus::encoding::Encoding;
+use Janus::scan::{self, Glob, ScanOptions, Summary, Verdict};
+use common::TempDir;
+use std::os::unix::fs::symlink;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+
+#[test]
+fn glob_matches() {
+    let cases: &[(&str, &[&str], &[&str])] = &[
+        // without a `/`, the file name at any depth
+        (
+            "*.txt",
+            &["a.txt", "x/y/a.txt", ".txt"],
+            &["a.txt.gz", "a.txt/b"],
+        ),
+        // `**/` at the start: no directory, or any number of them
+        (
+            "**/*.rs",
+            &["a.rs", "src/a.rs", "src/x/y/a.rs"],
+            &["a.rs.orig"],
+        ),
+        // and in the middle
+        (
+            "src/**/mod.rs",
+            &["src/mod.rs", "src/a/mod.rs", "src/a/b/mod.rs"],
+            &["mod.rs", "lib/src/mod.rs", "src/amod.rs"],
+        ),
+        // `*` and `?` stay within one component
+        (
+            "src/*.rs",
+            &["src/a.rs", "src/.rs"],
+            &["src/a/b.rs", "a.rs"],
+        ),
+        ("a/?/b", &["a/x/b"], &["a//b", "a/xy/b", "a/b"]),
+        // `**` elsewhere crosses them
+        ("docs/**", &["docs/a", "docs/a/b.md"], &["doc/a"]),
+        // classes, ranges and negation, never matching `/`
+        ("[a-c]?.txt", &["ax.txt", "c1.txt"], &["dx.txt", "a.txt"]),
+        ("[!a-c]?.txt", &["dx.txt", "_1.txt"], &["ax.txt", "c1.txt"]),
+        ("[^0-9]", &["a"], &["5"]),
+        ("x/[!a]y", &["x/by"], &["x//y", "x/ay"]),
+        ("[]x]", &["]", "x"], &["y"]),
+        // a `[` without its `]` is literal
+        ("[ab", &["[ab"], &["a", "b"]),
+    ];
+    for &(pattern, matching, other) in cases {
+        let glob = Glob::new(pattern);
+        for path in matching {
+            assert!(glob.matches(path), "{} should match {}", pattern, path);
+        }
+        for path in other {
+            assert!(!glob.matches(path), "{} should not match {}", pattern, path);
+        }
+    }
+}
+
+// root/good.txt             valid
+// root/bad.txt              invalid
+// root/sub/good.dat         valid
+// root/sub/loop -> root     a symlink loop
+// root/skip/bad.txt         invalid
+// root/link -> outside      a symlink to a directory out of the tree
+// outside/other.txt         invalid
+fn tree(dir: &TempDir) -> PathBuf {
+    let root = dir.path().join("root");
+    let (good, _, _) = encoding_rs::GBK.encode("中文");
+    dir.write("root/good.txt", &good);
+    dir.write("root/bad.txt", b"a\xB0");
+    dir.write("root/sub/good.dat", &good);
+    dir.write("root/skip/bad.txt", b"\xFF");
+    dir.write("outside/other.txt", b"\xB0\xB0\xB0");
+    symlink(&root, root.join("sub/loop")).unwrap();
+    symlink(dir.path().join("outside"), root.join("link")).unwrap();
+    root
+}
+
+fn run(root: &Path, options: &ScanOptions) -> (Summary, Vec<(String, bool)>) {
+    let reports = Mutex::new(Vec::new());
+    let summary = scan::scan(&[root.to_path_buf()], options, |file| {
+        let relative = file.path.strip_prefix(root).unwrap().to_string_lossy();
+        let valid = matches!(file.verdict, Verdict::Valid(_));
+        reports.lock().unwrap().push((relative.into_owned(), valid));
+    });
+    let mut reports = reports.into_inner().unwrap();
+    reports.sort();
+    (summary, reports)
+}
+
+fn report(path: &str, valid: bool) -> (String, bool) {
+    (path.to_string(), valid)
+}
+
+#[test]
+fn scan_tree() {
+    let dir = TempDir::new("scan-tree");
+    let root = tree(&dir);
+    let mut options = ScanOptions::new(vec![Encoding::Gb2312]);
+    options.threads = 2;
+
+    let (summary, reports) = run(&root, &options);
+    assert_eq!(
+        reports,
+        [
+            report("bad.txt", false),
+            report("good.txt", true),
+            report("skip/bad.txt", false),
+            report("sub/good.dat", true),
+        ]
+    );
+    assert_eq!(summary.valid, [2]);
+    assert_eq!((summary.invalid, summary.errors), (2, 0));
+    // both symlinks
+    assert_eq!(summary.skipped, 2);
+
+    // the loop leads back to a directory already entered
+    options.follow_symlinks = true;
+    let (summary, reports) = run(&root, &options);
+    assert_eq!(reports.len(), 5);
+    assert!(reports.contains(&report("link/other.txt", false)));
+    assert_eq!(summary.valid, [2]);
+    assert_eq!((summary.invalid, summary.skipped), (3, 0));
+
+    options.follow_symlinks = false;
+    options.exclude = vec![Glob::new("skip")];
+    options.include = vec![Glob::new("*.txt")];
+    let (summary, reports) = run(&root, &options);
+    assert_eq!(
+        reports,
+        [report("bad.txt", false), report("good.txt", true)]
+    );
+    assert_eq!((summary.valid[0], summary.invalid), (1, 1));
+
+    options.exclude = vec![Glob::new("**/bad.txt")];
+    options.include = Vec::new();
+    let (_, reports) = run(&root, &options);
+    assert_eq!(
+        reports,
+        [report("good.txt", true), report("sub/good.dat", true)]
+    );
+}
+
+fn janus_scan(args: &[&str], root: &Path) -> Option<i32> {
+    Command::new(env!("CARGO_BIN_EXE_janus"))
+        .arg("scan")
+        .args(args)
+        .arg(root)
+        .output()
+        .unwrap()
+        .status
+        .code()
+}
+
+#[test]
+fn scan_exit_codes() {
+    let dir = TempDir::new("scan-exit");
+    let root = tree(&dir);
+    let valid = ["-e", "gb2312", "--exclude", "bad.txt"];
+    assert_eq!(janus_scan(&valid, &root), Some(0));
+    assert_eq!(janus_scan(&["-e", "gb2312"], &root), Some(1));
+    // GB18030 accepts what GB2312 rejects
+    dir.write("root/gbk.txt", b"\x81\x40");
+    assert_eq!(janus_scan(&valid, &root), Some(1));
+    let valid = ["-e", "gb2312", "-e", "gb18030", "-x", "bad.txt"];
+    assert_eq!(janus_scan(&valid, &root), Some(0));
+
+    // a dangling symlink is skipped, or cannot be read when followed
+    symlink(dir.path().join("missing"), root.join("dangling")).unwrap();
+    assert_eq!(janus_scan(&valid, &root), Some(0));
+    assert_eq!(
+        janus_scan(&["-L", "-e", "gb2312,gb18030", "-x", "bad.txt"], &root),
+        Some(2)
+    );
+    assert_eq!(janus_scan(&valid, &dir.path().join("missing")), Some(2));
+
+    // usage errors
+    assert_eq!(janus_scan(&[], &root), Some(2));
+    assert_eq!(janus_scan(&["-e", "latin1"], &root), Some(2));
+    assert_eq!(janus_scan(&["-e", "gb2312", "-s", "fast"], &root), Some(2));
+}
@@ -0,0 +1,86 @@
+use Janus::encoding::Encoding;
+use Janus::model;
+use Janus::shorttext::{DEFAULT_MIN_CONFIDENCE, Disambiguator, Ranked, Verdict, disambiguate};
+
+fn encodings(ranking: &[Ranked]) -> Vec<Encoding> {
+    ranking.iter().map(|ranked| ranked.encoding).collect()
+}
+
+// Best first, with confidences that add up to 1.
+fn assert_ranking(ranking: &[Ranked]) {
+    let sum: f64 = ranking.iter().map(|ranked| ranked.confidence).sum();
+    assert!((sum - 1.0).abs() < 1e-9, "{ranking:?}");
+    assert!(
+        ranking
+            .windows(2)
+            .all(|pair| pair[0].confidence >= pair[1].confidence),
+        "{ranking:?}"
+    );
+}
+
+#[test]
+fn ranked() {
+    for (text, encoder, best) in [
+        ("안녕하세요", encoding_rs::EUC_KR, Encoding::EucKr),
+        ("中华人民共和国", encoding_rs::GBK, Encoding::Gb2312),
+        ("ありがとう", encoding_rs::EUC_JP, Encoding::EucJp),
+    ] {
+        let (data, _, _) = encoder.encode(text);
+        let verdict = disambiguate(&data);
+        let Verdict::Ranked(ranking) = &verdict else {
+            panic!("{text} {verdict:?}");
+        };
+        assert_ranking(ranking);
+        // all three accept each of them
+        assert_eq!(ranking.len(), 3, "{text}");
+        assert_eq!(verdict.best(), Some(best), "{text}");
+        assert!(ranking[0].confidence >= DEFAULT_MIN_CONFIDENCE);
+    }
+    // EUC-KR has hiragana in row 0xAA, which the others leave empty
+    let (data, _, _) = encoding_rs::EUC_KR.encode("ありがとう");
+    assert_eq!(
+        disambiguate(&data),
+        Verdict::Ranked(vec![Ranked {
+            encoding: Encoding::EucKr,
+            confidence: 1.0
+        }])
+    );
+}
+
+#[test]
+fn undecidable() {
+    // 中文 in EUC-JP reads nearly as well in GB2312
+    let data = b"\xC3\xE6\xCA\xB8";
+    let Verdict::Undecidable(ranking) = disambiguate(data) else {
+        panic!();
+    };
+    assert_ranking(&ranking);
+    assert_eq!(ranking[0].encoding, Encoding::EucJp);
+    assert!(ranking[0].confidence < DEFAULT_MIN_CONFIDENCE);
+    assert_eq!(disambiguate(data).best(), None);
+
+    // the same ranking passes a lower minimum
+    let lenient = Disambiguator::new(model::default_models().to_vec(), 0.5);
+    assert_eq!(lenient.disambiguate(data), Verdict::Ranked(ranking));
+
+    // ASCII tells the candidates nothing, whatever the minimum
+    for data in [&b""[..], b"hello"] {
+        let Verdict::Undecidable(ranking) = lenient.disambiguate(data) else {
+            panic!("{data:?}");
+        };
+        assert_eq!(
+            encodings(&ranking),
+            [Encoding::Gb2312, Encoding::EucKr, Encoding::EucJp]
+        );
+    }
+}
+
+#[test]
+fn invalid() {
+    // a cut pair, a byte no table has, a pair outside all three and
+    // JIS X 0212 cut short
+    for data in [&b"\xB0"[..], b"a\xFF", b"\xA1\xA1\xFE\xA1", b"\x8F\xB0"] {
+        assert_eq!(disambiguate(data), Verdict::Invalid, "{data:02x?}");
+        assert_eq!(disambiguate(data).best(), None);
+    }
+}
@@ -0,0 +1,98 @@
+mod common;
+
+use Janus::encoding::Encoding;
+use Janus::error::{ErrorKind, ValidationError};
+use Janus::stream::StreamValidator;
+use common::{Rng, corrupt, sample};
+
+// Feeds `data` cut at `cuts`, stopping at the first error as a caller
+// would, then finishes the stream.
+fn stream(data: &[u8], encoding: Encoding, cuts: &[usize]) -> Result<(), ValidationError> {
+    let mut validator = StreamValidator::new(encoding);
+    let mut start = 0;
+    for &end in cuts.iter().chain([&data.len()]) {
+        validator.feed(&data[start..end])?;
+        assert!(validator.valid_up_to() <= end);
+        start = end;
+    }
+    validator.finish()?;
+    assert_eq!(validator.valid_up_to(), data.len());
+    Ok(())
+}
+
+#[test]
+fn any_chunking_matches_validate() {
+    let mut rng = Rng(0xBF58_476D_1CE4_E5B9);
+    for encoding in Encoding::ALL {
+        for _ in 0..300 {
+            let pieces = 1 + rng.below(12);
+            let mut data = sample(&mut rng, encoding, pieces);
+            if rng.below(2) == 0 {
+                corrupt(&mut rng, &mut data);
+            }
+            let expected = encoding.validate(&data);
+            // one byte at a time
+            let bytes: Vec<usize> = (1..data.len()).collect();
+            assert_eq!(
+                stream(&data, encoding, &bytes),
+                expected,
+                "{encoding} {data:02x?}"
+            );
+            // two chunks, split at every position, characters included
+            for cut in 0..=data.len() {
+                assert_eq!(
+                    stream(&data, encoding, &[cut]),
+                    expected,
+                    "{encoding} {cut}"
+                );
+            }
+            // random chunks, empty ones included
+            let mut cuts: Vec<usize> = (0..rng.below(6))
+                .map(|_| rng.below(data.len() + 1))
+                .collect();
+            cuts.sort();
+            assert_eq!(
+                stream(&data, encoding, &cuts),
+                expected,
+                "{encoding} {cuts:?}"
+            );
+        }
+    }
+}
+
+// A character whose bytes arrive over several chunks fails at its start,
+// not in the chunk where it breaks.
+#[test]
+fn error_spans_chunks() {
+    let mut validator = StreamValidator::new(Encoding::Gb18030);
+    validator.feed(b"ab\x81").unwrap();
+    validator.feed(b"\x30").unwrap();
+    assert_eq!(validator.pending(), b"\x81\x30");
+    validator.feed(b"\x81").unwrap();
+    assert_eq!(validator.valid_up_to(), 2);
+    let error = validator.feed(b"\x20cd").unwrap_err();
+    assert_eq!(error.offset, 2);
+    assert_eq!(validator.valid_up_to(), 2);
+    // every later call reports the same error
+    assert_eq!(validator.feed(b"ok"), Err(error));
+    assert_eq!(validator.finish(), Err(error));
+    assert_eq!(
+        Encoding::Gb18030.validate(b"ab\x81\x30\x81\x20cd"),
+        Err(error)
+    );
+
+    // a character left pending at the end is truncated
+    let mut validator = StreamValidator::new(Encoding::Utf8);
+    validator.feed("中".as_bytes()).unwrap();
+    validator.feed(&"文".as_bytes()[..2]).unwrap();
+    let error = validator.finish().unwrap_err();
+    assert_eq!((error.offset, error.kind), (3, ErrorKind::Truncated));
+
+    // a surrogate pair split between its units
+    let mut validator = StreamValidator::new(Encoding::Utf16Le);
+    validator.feed(b"a\x00\x3D").unwrap();
+    validator.feed(b"\xD8\x00").unwrap();
+    validator.feed(b"\xDE").unwrap();
+    assert_eq!(validator.finish(), Ok(()));
+    assert_eq!(validator.valid_up_to(), 6);
+}
@@ -0,0 +1,83 @@
+use Janus::encoding::Encoding;
+use Janus::writer::ValidatingWriter;
+use std::io::{self, Write};
+
+// Fails every `period`-th call to `write`, and takes at most `max` bytes
+// per call otherwise.
+struct Flaky {
+    out: Vec<u8>,
+    calls: usize,
+    period: usize,
+    max: usize,
+}
+
+impl Write for Flaky {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.calls += 1;
+        if self.calls.is_multiple_of(self.period) {
+            return Err(io::Error::other("flaky"));
+        }
+        let n = buf.len().min(self.max);
+        self.out.extend_from_slice(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// Writes `data` in chunks of `chunk` bytes, retrying after errors.
+fn write_through(data: &[u8], chunk: usize, period: usize, max: usize) -> Vec<u8> {
+    let inner = Flaky {
+        out: Vec::new(),
+        calls: 0,
+        period,
+        max,
+    };
+    let mut writer = ValidatingWriter::new(inner, Encoding::Gb2312);
+    let mut rest = data;
+    while !rest.is_empty() {
+        let end = chunk.min(rest.len());
+        if let Ok(n) = writer.write(&rest[..end]) {
+            rest = &rest[n..];
+        }
+    }
+    while writer.flush().is_err() {}
+    writer.get_ref().out.clone()
+}
+
+#[test]
+fn inner_failures_lose_nothing() {
+    let (data, _, _) = encoding_rs::GBK.encode("编码 validation 测试，一二三四五六七八九十。");
+    for chunk in 1..=7 {
+        for period in 2..=5 {
+            for max in [1, 3, usize::MAX] {
+                assert_eq!(
+                    write_through(&data, chunk, period, max),
+                    *data,
+                    "{chunk} {period} {max}"
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn fail_once_then_succeed() {
+    let inner = Flaky {
+        out: Vec::new(),
+        calls: 0,
+        period: 1,
+        max: usize::MAX,
+    };
+    let mut writer = ValidatingWriter::new(inner, Encoding::Gb2312);
+    let (data, _, _) = encoding_rs::GBK.encode("测试 abc");
+    // every call to the inner writer fails until `period` is raised
+    writer.write_all(&data[..5]).unwrap();
+    assert!(writer.flush().is_err());
+    writer.get_mut().period = usize::MAX;
+    writer.write_all(&data[5..]).unwrap();
+    let inner = writer.finish().unwrap();
+    assert_eq!(inner.out, *data);
+}